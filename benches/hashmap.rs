@@ -0,0 +1,120 @@
+//! Compares `HashMap` and `RobinHoodMap` at high load factors.
+//!
+//! Run with `cargo bench --bench hashmap`. Both tables get the same number of
+//! slots and are filled to the given fraction of them without growing, then
+//! timed on inserts, lookups of present keys, lookups of missing keys and
+//! removals. `HashMap` is created with a maximum load factor above the highest
+//! load, since its default of 3/4 would make it grow.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crust_std::hashmap::{HashMap, RobinHoodMap};
+
+const SLOTS: usize = 1 << 16;
+const ROUNDS: u32 = 20;
+/// Lets `HashMap` hold every load below without growing. `RobinHoodMap` holds
+/// up to 7/8 of its slots.
+const MAX_LOAD_FACTOR: f64 = 0.9;
+
+/// Generates `n` distinct pseudo-random keys.
+fn keys(n: usize, seed: u64) -> Vec<u64> {
+    let mut state = seed;
+    let mut keys: Vec<u64> = (0..n)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            state | 1
+        })
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys
+}
+
+/// Runs `f` `ROUNDS` times and returns the mean time per key.
+fn time<F: FnMut()>(n: usize, mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    start.elapsed() / (ROUNDS * n as u32)
+}
+
+/// Like [`time`], but calls `setup` before each round and only times `f`.
+fn time_with_setup<T, S: FnMut() -> T, F: FnMut(T)>(n: usize, mut setup: S, mut f: F) -> Duration {
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..ROUNDS {
+        let input = setup();
+        let start = Instant::now();
+        f(input);
+        elapsed += start.elapsed();
+    }
+    elapsed / (ROUNDS * n as u32)
+}
+
+macro_rules! bench_map {
+    ($name:literal, $new:expr, $len:expr) => {{
+        let present = keys($len, 1);
+        // Even keys never collide with the odd keys in `present`.
+        let missing: Vec<u64> = keys($len, 2).into_iter().map(|key| key << 1).collect();
+        let n = present.len();
+
+        let insert = time(n, || {
+            let mut map = $new(n);
+            for &key in &present {
+                map.insert(key, key);
+            }
+            black_box(map);
+        });
+
+        let mut map = $new(n);
+        for &key in &present {
+            map.insert(key, key);
+        }
+        assert_eq!(map.slots().len(), SLOTS);
+        let hit = time(n, || {
+            for key in &present {
+                black_box(map.get(key));
+            }
+        });
+        let miss = time(n, || {
+            for key in &missing {
+                black_box(map.get(key));
+            }
+        });
+        let remove = time_with_setup(
+            n,
+            || map.clone(),
+            |mut map| {
+                for key in &present {
+                    black_box(map.remove(key));
+                }
+                black_box(map);
+            },
+        );
+
+        println!(
+            "{:<12} {:>6} {:>10.1?} {:>10.1?} {:>10.1?} {:>10.1?}",
+            $name, n, insert, hit, miss, remove
+        );
+    }};
+}
+
+fn main() {
+    println!(
+        "{:<12} {:>6} {:>10} {:>10} {:>10} {:>10}",
+        "map", "len", "insert", "hit", "miss", "remove"
+    );
+    for load in [0.5, 0.7, 0.75, 0.8, 0.875] {
+        let len = (SLOTS as f64 * load) as usize;
+        println!("load factor {load}");
+        bench_map!(
+            "HashMap",
+            |n| HashMap::<u64, u64>::with_capacity_and_max_load_factor(n, MAX_LOAD_FACTOR),
+            len
+        );
+        bench_map!("RobinHoodMap", RobinHoodMap::<u64, u64>::with_capacity, len);
+    }
+}
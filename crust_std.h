@@ -6,11 +6,110 @@
 #include <cstdlib>
 #include <ostream>
 #include <new>
+#include <iterator>
+#include <optional>
 #include <span>
+#include <string_view>
+#include <type_traits>
+#include <utility>
+namespace iceberg {
+
+/// The C functions of an exported `HashMap<K, V>` instantiation. It is
+/// specialized by `CRUST_EXPORT_HASHMAP`.
+template<typename K, typename V>
+struct HashMapOps;
+
+template<typename T>
+struct OwnedVec;
+
+/// The drop function of an exported `OwnedBox<T>` instantiation. It is
+/// specialized by `CRUST_EXPORT_OWNED_BOX`.
+template<typename T>
+struct OwnedBoxOps;
+
+/// A struct that replaces a `Box<T>`, so that recursive types can be
+/// described to cbindgen. It is `#[repr(transparent)]` in Rust, which
+/// cbindgen would turn into a typedef, so it is written here by hand.
+///
+/// It can only be destroyed for instantiations exported with
+/// `CRUST_EXPORT_OWNED_BOX`, whose drop function runs on the Rust side.
+template<typename T>
+struct OwnedBox {
+  T *ptr;
+
+  OwnedBox(const OwnedBox &) = delete;
+  OwnedBox &operator=(const OwnedBox &) = delete;
+
+  /// Takes the value of `other`, which must not be used afterwards except to
+  /// destroy it.
+  OwnedBox(OwnedBox &&other) : ptr(other.ptr) {
+    other.ptr = nullptr;
+  }
+
+  OwnedBox &operator=(OwnedBox &&other) {
+    if (this != &other) {
+      this->~OwnedBox();
+      ::new (this) OwnedBox(std::move(other));
+    }
+    return *this;
+  }
+
+  T &operator*() const {
+    return *ptr;
+  }
+
+  T *operator->() const {
+    return ptr;
+  }
+
+  ~OwnedBox() {
+    if (ptr)
+      OwnedBoxOps<T>::drop(this);
+    ptr = nullptr;
+  }
+};
+
+/// Declared early for the destructors of `OwnedSlice` and `OwnedVec`.
+extern "C" void crust_dealloc(uint8_t *ptr, size_t size, size_t align);
+
+struct ArcHeader;
+
+/// Declared early for the copy constructor and destructor of `ArcSlice`.
+extern "C" void crust_arc_slice_retain(const ArcHeader *header);
+extern "C" bool crust_arc_slice_release(const ArcHeader *header);
+extern "C" void crust_arc_slice_free(const ArcHeader *header);
+
+/// Declared early for `Str::FromUtf8`.
+extern "C" bool crust_str_validate(const uint8_t *ptr, size_t len);
+
+/// Declared early for `OwnedVec::reserve`.
+extern "C" void crust_owned_vec_reserve(OwnedVec<uint8_t> *vec,
+                                        size_t additional,
+                                        size_t size,
+                                        size_t align);
+
+} // namespace iceberg
 
 
 namespace iceberg {
 
+/// The multiplier of the Fx hash function.
+static const uint64_t CRUST_FXHASH_SEED = 5871781006564002453;
+
+/// Identifies the hash function used by `HashMap` by default. It is bumped
+/// whenever the algorithm of this module changes, so both sides of the FFI
+/// boundary can check that they agree.
+static const uint32_t CRUST_HASHER_ID = 2;
+
+/// The reference count in front of the elements of an [`ArcSlice`]. It also
+/// records the layout of the allocation, so that it can be released without
+/// knowing the element type.
+struct ArcHeader;
+
+/// Builds [`FxHasher`]s. This is the default hasher of
+/// [`crate::hashmap::HashMap`].
+struct FxBuildHasher;
+
 /// A struct that basically replaces a `Box<[T]>`, but which cbindgen can
 /// understand.
 ///
@@ -21,10 +120,38 @@ namespace iceberg {
 /// But handling fat pointers with cbindgen both in structs and argument
 /// positions more generally is a bit tricky.
 ///
+/// The elements are allocated by Rust's global allocator, whatever it is. C++
+/// must allocate and release them through [`crust_alloc`] and
+/// [`crust_dealloc`], never with `malloc` and `free`.
+///
 template<typename T>
 struct OwnedSlice {
   T *ptr;
   size_t len;
+  /// An empty slice, which does not allocate.
+  OwnedSlice() : ptr(reinterpret_cast<T*>(alignof(T))), len(0) {}
+
+  /// Takes ownership of `len` elements at `ptr`, which must come from
+  /// `crust_alloc` with the size of exactly `len` elements.
+  OwnedSlice(T *ptr, size_t len) : ptr(ptr), len(len) {}
+
+  OwnedSlice(const OwnedSlice &) = delete;
+  OwnedSlice &operator=(const OwnedSlice &) = delete;
+
+  /// Takes the elements of `other` and leaves it empty, like `mem::take`.
+  OwnedSlice(OwnedSlice &&other) : ptr(other.ptr), len(other.len) {
+    other.ptr = reinterpret_cast<T*>(alignof(T));
+    other.len = 0;
+  }
+
+  OwnedSlice &operator=(OwnedSlice &&other) {
+    if (this != &other) {
+      this->~OwnedSlice();
+      ::new (this) OwnedSlice(std::move(other));
+    }
+    return *this;
+  }
+
   std::span<T> AsSpan() {
     return { ptr, len };
   }
@@ -38,7 +165,7 @@ struct OwnedSlice {
       return;
     for (auto& val : AsSpan())
       val.~T();
-    free(ptr);
+    crust_dealloc(reinterpret_cast<uint8_t*>(ptr), len * sizeof(T), alignof(T));
     ptr = (T*)alignof(T);
     len = 0; 
   }
@@ -48,6 +175,149 @@ struct OwnedSlice {
 /// suitable for FFI.
 using OwnedStr = OwnedSlice<uint8_t>;
 
+/// A growable vector with a layout that cbindgen can describe.
+///
+/// It is a `Vec<T>` taken apart into its pointer, length and capacity, so it
+/// converts to and from `Vec<T>` without copying. Converting from an
+/// [`OwnedSlice`] is free as well, while converting to one shrinks the
+/// allocation to the length first.
+///
+/// C++ appends through `push_back`, which grows the buffer with
+/// [`crust_owned_vec_reserve`], so the elements always live in memory of
+/// Rust's global allocator. Growing moves the elements bytewise, so C++ may
+/// only store types that Rust could move the same way.
+///
+template<typename T>
+struct OwnedVec {
+  T *ptr;
+  size_t len;
+  size_t cap;
+  /// An empty vector, which does not allocate.
+  OwnedVec() : ptr(reinterpret_cast<T*>(alignof(T))), len(0), cap(0) {}
+
+  OwnedVec(const OwnedVec &) = delete;
+  OwnedVec &operator=(const OwnedVec &) = delete;
+
+  /// Takes the elements of `other` and leaves it empty, like `mem::take`.
+  OwnedVec(OwnedVec &&other) : ptr(other.ptr), len(other.len), cap(other.cap) {
+    other.ptr = reinterpret_cast<T*>(alignof(T));
+    other.len = 0;
+    other.cap = 0;
+  }
+
+  OwnedVec &operator=(OwnedVec &&other) {
+    if (this != &other) {
+      this->~OwnedVec();
+      ::new (this) OwnedVec(std::move(other));
+    }
+    return *this;
+  }
+
+  std::span<T> AsSpan() {
+    return { ptr, len };
+  }
+
+  inline std::span<const T> AsSpan() const {
+    return { ptr, len };
+  }
+
+  /// Makes room for at least `additional` more elements. The buffer is
+  /// reallocated by Rust, which moves the elements bytewise, so `T` must be
+  /// a type that Rust could move as well, such as the types of this header.
+  void reserve(size_t additional) {
+    crust_owned_vec_reserve(reinterpret_cast<OwnedVec<uint8_t>*>(this), additional, sizeof(T),
+                            alignof(T));
+  }
+
+  /// Move-constructs `value` at the back of the vector.
+  void push_back(T &&value) {
+    reserve(1);
+    ::new (static_cast<void*>(ptr + len)) T(std::move(value));
+    ++len;
+  }
+
+  ~OwnedVec() {
+    for (auto& val : AsSpan())
+      val.~T();
+    if (cap)
+      crust_dealloc(reinterpret_cast<uint8_t*>(ptr), cap * sizeof(T), alignof(T));
+    ptr = (T*)alignof(T);
+    len = 0;
+    cap = 0;
+  }
+};
+
+/// A struct that replaces an `Arc<[T]>`, but which cbindgen can understand.
+///
+/// The header and the elements share one allocation. Cloning only bumps the
+/// reference count, on either side of the FFI boundary: the C++ copy
+/// constructor calls [`crust_arc_slice_retain`], and the destructor calls
+/// [`crust_arc_slice_release`]. Empty slices do not allocate, and their
+/// header is null.
+///
+template<typename T>
+struct ArcSlice {
+  const ArcHeader *header;
+  const T *ptr;
+  size_t len;
+  ArcSlice() : header(nullptr), ptr(nullptr), len(0) {}
+
+  ArcSlice(const ArcSlice &other) : header(other.header), ptr(other.ptr), len(other.len) {
+    crust_arc_slice_retain(header);
+  }
+
+  ArcSlice(ArcSlice &&other) : header(other.header), ptr(other.ptr), len(other.len) {
+    other.header = nullptr;
+    other.ptr = nullptr;
+    other.len = 0;
+  }
+
+  ArcSlice &operator=(const ArcSlice &other) {
+    return *this = ArcSlice(other);
+  }
+
+  ArcSlice &operator=(ArcSlice &&other) {
+    if (this != &other) {
+      this->~ArcSlice();
+      ::new (this) ArcSlice(std::move(other));
+    }
+    return *this;
+  }
+
+  std::span<const T> AsSpan() const {
+    return { ptr, len };
+  }
+
+  ~ArcSlice() {
+    if (crust_arc_slice_release(header)) {
+      for (auto& val : AsSpan())
+        val.~T();
+      crust_arc_slice_free(header);
+    }
+    header = nullptr;
+    ptr = nullptr;
+    len = 0;
+  }
+};
+
+/// A struct that basically replaces an Arc<str>, but with a defined layout,
+/// suitable for FFI. Cloning it only bumps the reference count.
+using ArcStr = ArcSlice<uint8_t>;
+
+/// The hasher used by [`HashMap`] unless another one is chosen.
+///
+/// It is a stable, documented hash function (see [`crate::fxhash`]), so the
+/// placement of entries is the same for every toolchain and on both sides of
+/// the FFI boundary. Its identity is exported as
+/// [`CRUST_HASHER_ID`](crate::fxhash::CRUST_HASHER_ID).
+using DefaultHashBuilder = FxBuildHasher;
+
+template<typename K, typename V>
+struct KeyValue {
+  K key;
+  V value;
+};
+
 template<typename T>
 struct Option {
   enum class Tag : uint8_t {
@@ -80,11 +350,447 @@ struct Option {
   }
 };
 
+/// A hash map with a layout that cbindgen can describe.
+///
+/// The table follows the SwissTable design: next to the slots there is an
+/// array of control bytes, one per slot, that holds a 7-bit tag of the hash
+/// of every entry. Lookups scan the control bytes a group at a time (with
+/// SSE2 where available) and only compare keys whose tag matches. The slots
+/// themselves stay `Option`s, so the entries can be walked and dropped from
+/// C++ without looking at the control bytes.
+///
+/// The hasher `S` is a type-level choice and is not stored in the map: every
+/// hash is computed with a hasher built from `S::default()`. This keeps the
+/// `#[repr(C)]` layout independent of the hasher, but it also means that `S`
+/// must build identical hashers every time. Randomly seeded builders such as
+/// `std::collections::hash_map::RandomState` must not be used. For the same
+/// reason there are no constructors that take a hash builder: a map with
+/// another hasher is created with [`Default`] and grown with
+/// [`reserve`](HashMap::reserve).
+///
+/// Two maps are equal if they hold the same entries, regardless of
+/// insertion order, capacity or load factor.
+///
+/// `Debug` prints the entries like a map. Use
+/// [`debug_slots`](HashMap::debug_slots) to inspect the table itself.
+template<typename K, typename V, typename S>
+struct HashMap {
+  OwnedSlice<Option<KeyValue<K, V>>> data;
+  OwnedSlice<uint8_t> ctrl;
+  size_t n_items;
+  size_t growth_left;
+  size_t bucket_mask;
+  size_t max_load;
+  /// Only available for instantiations exported with `CRUST_EXPORT_HASHMAP`.
+  using Ops = HashMapOps<K, V>;
+
+  /// Initializes uninitialized storage as an empty map.
+  void init() {
+    Ops::init(this);
+  }
+
+  size_t size() const {
+    return Ops::len(this);
+  }
+
+  /// Returns the value for `key`, or null if it is missing.
+  const V *get(const K &key) const {
+    return Ops::get(this, &key);
+  }
+
+  bool contains(const K &key) const {
+    return Ops::contains(this, &key);
+  }
+
+  /// Moves the key and value into the map and returns whether the key was
+  /// new. An existing value is replaced.
+  bool insert(K &&key, V &&value) {
+    return Ops::insert(this, &key, &value);
+  }
+
+  /// Removes `key` and returns whether it was present. The removed value is
+  /// constructed in the uninitialized storage at `out_value` unless it is
+  /// null.
+  bool remove(const K &key, V *out_value = nullptr) {
+    return Ops::remove(this, &key, out_value);
+  }
+
+  /// Calls `f(key, value)` for every entry.
+  template<typename F>
+  void for_each(F &&f) const {
+    size_t cursor = 0;
+    while (const KeyValue<K, V> *entry = Ops::next(this, &cursor)) {
+      f(entry->key, entry->value);
+    }
+  }
+
+  /// Drops all entries and releases the table on the Rust side.
+  void clear() {
+    Ops::free(this);
+  }
+
+  /// Walks the slots in table order and stops at the occupied ones. It only
+  /// reads the layout, so unlike the methods above it works for every
+  /// instantiation. Keys must not be modified through it.
+  template<typename Slot, typename Entry>
+  class Iterator {
+   public:
+    using iterator_category = std::forward_iterator_tag;
+    using value_type = KeyValue<K, V>;
+    using difference_type = std::ptrdiff_t;
+    using pointer = Entry *;
+    using reference = Entry &;
+
+    Iterator() = default;
+
+    Iterator(Slot *slot, Slot *last) : slot(slot), last(last) {
+      skip_empty();
+    }
+
+    reference operator*() const {
+      return slot->some._0;
+    }
+
+    pointer operator->() const {
+      return &slot->some._0;
+    }
+
+    Iterator &operator++() {
+      ++slot;
+      skip_empty();
+      return *this;
+    }
+
+    Iterator operator++(int) {
+      Iterator old = *this;
+      ++*this;
+      return old;
+    }
+
+    bool operator==(const Iterator &other) const {
+      return slot == other.slot;
+    }
+
+    bool operator!=(const Iterator &other) const {
+      return slot != other.slot;
+    }
+
+   private:
+    void skip_empty() {
+      while (slot != last && slot->tag == Option<KeyValue<K, V>>::Tag::None) {
+        ++slot;
+      }
+    }
+
+    Slot *slot = nullptr;
+    Slot *last = nullptr;
+  };
+
+  using iterator = Iterator<Option<KeyValue<K, V>>, KeyValue<K, V>>;
+  using const_iterator = Iterator<const Option<KeyValue<K, V>>, const KeyValue<K, V>>;
+
+  iterator begin() {
+    return iterator(data.ptr, data.ptr + data.len);
+  }
+
+  iterator end() {
+    return iterator(data.ptr + data.len, data.ptr + data.len);
+  }
+
+  const_iterator begin() const {
+    return const_iterator(data.ptr, data.ptr + data.len);
+  }
+
+  const_iterator end() const {
+    return const_iterator(data.ptr + data.len, data.ptr + data.len);
+  }
+};
+
+/// The value type of the map behind a [`HashSet`].
+///
+/// It takes no space in Rust. C++ cannot express that for a field, so the
+/// header specializes `KeyValue<K, SetValue>` to hold only the key.
+struct SetValue {
+
+};
+
+/// A hash set with a layout that cbindgen can describe.
+///
+/// It is a [`HashMap`] with [`SetValue`] values, so it shares the table
+/// layout, hashing and probing of the map. `SetValue` takes no space, so a
+/// slot of `HashSet<T>` has the same layout as an `Option<T>`.
+///
+/// Two sets are equal if they hold the same elements, regardless of
+/// insertion order or capacity.
+template<typename T, typename S>
+struct HashSet {
+  HashMap<T, SetValue, S> map;
+};
+
+/// A hash map that keeps its entries in insertion order, with a layout that
+/// cbindgen can describe.
+///
+/// The entries live in a plain `OwnedVec<KeyValue<K, V>>`, so C++ can walk
+/// them as a span, in order, without knowing about the hash index. The index
+/// is a separate open-addressed table of positions into the entries, probed
+/// linearly.
+///
+/// If hashing, comparing keys or a callback panics, the index is rebuilt
+/// from the entries, so the map stays usable.
+///
+/// Two maps are equal if they hold the same entries, regardless of their
+/// order.
+template<typename K, typename V, typename S>
+struct IndexMap {
+  OwnedVec<KeyValue<K, V>> entries;
+  OwnedSlice<size_t> indices;
+};
+
+/// A struct that replaces a `&'a [T]`, but which cbindgen can understand.
+///
+/// C++ converts a `std::span` into it implicitly, so a buffer owned by C++ can
+/// be handed to Rust without copying it. The pointer may be null if the slice
+/// is empty, as it is for an empty `std::span`.
+template<typename T>
+struct Slice {
+  const T *ptr;
+  size_t len;
+  Slice() : ptr(nullptr), len(0) {}
+
+  Slice(const T *ptr, size_t len) : ptr(ptr), len(len) {}
+
+  Slice(std::span<const T> span) : ptr(span.data()), len(span.size()) {}
+
+  /// Accepts anything that converts to a `std::span`, such as a `std::vector`.
+  template<typename R>
+    requires std::is_convertible_v<const R&, std::span<const T>>
+  Slice(const R &range) : Slice(std::span<const T>(range)) {}
+
+  std::span<const T> AsSpan() const {
+    return { ptr, len };
+  }
+
+  operator std::span<const T>() const {
+    return AsSpan();
+  }
+};
+
+/// A struct that replaces a `&'a mut [T]`, but which cbindgen can
+/// understand.
+///
+/// C++ converts a `std::span` of mutable elements into it implicitly. The
+/// pointer may be null if the slice is empty.
+template<typename T>
+struct SliceMut {
+  T *ptr;
+  size_t len;
+  SliceMut() : ptr(nullptr), len(0) {}
+
+  SliceMut(T *ptr, size_t len) : ptr(ptr), len(len) {}
+
+  SliceMut(std::span<T> span) : ptr(span.data()), len(span.size()) {}
+
+  /// Accepts anything that converts to a `std::span`, such as a `std::vector`.
+  template<typename R>
+    requires std::is_convertible_v<R&, std::span<T>>
+  SliceMut(R &range) : SliceMut(std::span<T>(range)) {}
+
+  std::span<T> AsSpan() const {
+    return { ptr, len };
+  }
+
+  operator std::span<T>() const {
+    return AsSpan();
+  }
+};
+
+/// A map that keeps its entries sorted by key, with a layout that cbindgen
+/// can describe.
+///
+/// The entries live in a plain `OwnedVec<KeyValue<K, V>>` in ascending key
+/// order, so C++ can walk them as a span and binary search them itself.
+/// Lookups are binary searches. Inserts and removals shift the following
+/// entries, so build large maps with [`FromIterator`] or [`Extend`], which
+/// sort once per call.
+///
+/// A panic in a closure, an iterator or `Ord::cmp` never loses entries that
+/// were in the map. Only a panicking `Ord::cmp` in [`Extend`] can leave them
+/// out of order.
+template<typename K, typename V>
+struct SortedMap {
+  OwnedVec<KeyValue<K, V>> entries;
+};
+
+/// A struct that replaces a `&'a str`, but which cbindgen can understand.
+///
+/// C++ creates it from a `std::string_view` with `Str::FromUtf8`, which
+/// checks the bytes with [`crust_str_validate`], or with
+/// `Str::FromUtf8Unchecked` if they are known to be UTF-8. Rust code that
+/// cannot rely on the latter checks the bytes with
+/// [`validate`](Str::validate).
+struct Str {
+  const uint8_t *ptr;
+  size_t len;
+  Str() : ptr(nullptr), len(0) {}
+
+  /// Borrows `view` if its bytes are valid UTF-8.
+  static std::optional<Str> FromUtf8(std::string_view view) {
+    if (!crust_str_validate(reinterpret_cast<const uint8_t*>(view.data()), view.size()))
+      return std::nullopt;
+    return FromUtf8Unchecked(view);
+  }
+
+  /// Borrows `view` without checking it. Its bytes must be valid UTF-8, since
+  /// Rust reads them as a `str`.
+  static Str FromUtf8Unchecked(std::string_view view) {
+    Str s;
+    s.ptr = reinterpret_cast<const uint8_t*>(view.data());
+    s.len = view.size();
+    return s;
+  }
+
+  std::string_view AsStringView() const {
+    return { reinterpret_cast<const char*>(ptr), len };
+  }
+
+  operator std::string_view() const {
+    return AsStringView();
+  }
+};
+
 
 extern "C" {
 
+/// Allocates `size` bytes aligned to `align` with Rust's global allocator, so
+/// that C++ can build an `OwnedSlice` that Rust is able to drop. Returns null
+/// if the allocation fails, and a dangling, aligned pointer if `size` is zero.
+///
+/// # Safety
+///
+/// `align` must be a power of two, and `size` rounded up to `align` must not
+/// overflow `isize`.
+uint8_t *crust_alloc(size_t size, size_t align);
+
+/// Releases the allocation behind `header` after the last reference was
+/// released and the elements were dropped.
+///
+/// # Safety
+///
+/// [`crust_arc_slice_release`] must have returned true for `header`.
+void crust_arc_slice_free(const ArcHeader *header);
+
+/// Decrements the reference count behind `header` and returns whether that
+/// released the last reference. In that case the caller must drop the
+/// elements and then call [`crust_arc_slice_free`]. Returns false if `header`
+/// is null.
+///
+/// # Safety
+///
+/// `header` must be null or belong to a live `ArcSlice`, whose reference is
+/// given up.
+bool crust_arc_slice_release(const ArcHeader *header);
+
+/// Increments the reference count behind `header`. Does nothing if `header`
+/// is null.
+///
+/// # Safety
+///
+/// `header` must be null or belong to a live `ArcSlice`.
+void crust_arc_slice_retain(const ArcHeader *header);
+
+/// Releases memory allocated by Rust's global allocator, such as the elements
+/// of an `OwnedSlice<T>`, which take `len * sizeof(T)` bytes aligned to
+/// `alignof(T)`. Does nothing if `size` is zero.
+///
+/// # Safety
+///
+/// Unless `size` is zero, `ptr` must have been allocated by [`crust_alloc`]
+/// or the global allocator with the same `size` and `align`.
+void crust_dealloc(uint8_t *ptr, size_t size, size_t align);
+
 uint64_t crust_hash_owned_str(const OwnedStr *s);
 
+/// Makes room for at least `additional` more elements in `vec`, whose
+/// elements take `size` bytes each and are aligned to `align`. C++ calls it
+/// for every `OwnedVec<T>`, cast to `OwnedVec<uint8_t>`, because the layout
+/// of the vector does not depend on `T`.
+///
+/// The capacity at least doubles when the vector grows, so appending is
+/// amortized constant time. Aborts if the capacity overflows or the
+/// allocation fails.
+///
+/// # Safety
+///
+/// `vec` must have been created by Rust for elements of `size` bytes aligned
+/// to `align`, and `size` must be a multiple of `align`.
+void crust_owned_vec_reserve(OwnedVec<uint8_t> *vec, size_t additional, size_t size, size_t align);
+
+/// Returns whether the `len` bytes at `ptr` are valid UTF-8. C++ calls it in
+/// `Str::FromUtf8`.
+///
+/// # Safety
+///
+/// `ptr` must be valid for reads of `len` bytes, or `len` must be zero.
+bool crust_str_validate(const uint8_t *ptr, size_t len);
+
 } // extern "C"
 
 } // namespace iceberg
+
+namespace iceberg {
+
+/// `SetValue` takes no space in Rust, so the slots of a `HashSet` hold only
+/// the key.
+template<typename K>
+struct KeyValue<K, SetValue> {
+  K key;
+};
+
+} // namespace iceberg
+
+/// Declares the functions that `export_hashmap!(K, V, prefix = "PREFIX")`
+/// generates in Rust and makes them available as methods of
+/// `iceberg::HashMap<K, V, iceberg::DefaultHashBuilder>`. Use it at global
+/// scope, once for every instantiation that is exported.
+#define CRUST_EXPORT_HASHMAP(K, V, PREFIX)                                                         \
+  extern "C" {                                                                                     \
+  void crust_hashmap_##PREFIX##_init(iceberg::HashMap<K, V, iceberg::DefaultHashBuilder> *map);    \
+  size_t crust_hashmap_##PREFIX##_len(                                                             \
+      const iceberg::HashMap<K, V, iceberg::DefaultHashBuilder> *map);                             \
+  const V *crust_hashmap_##PREFIX##_get(                                                           \
+      const iceberg::HashMap<K, V, iceberg::DefaultHashBuilder> *map, const K *key);               \
+  bool crust_hashmap_##PREFIX##_contains(                                                          \
+      const iceberg::HashMap<K, V, iceberg::DefaultHashBuilder> *map, const K *key);               \
+  bool crust_hashmap_##PREFIX##_insert(                                                            \
+      iceberg::HashMap<K, V, iceberg::DefaultHashBuilder> *map, K *key, V *value);                 \
+  bool crust_hashmap_##PREFIX##_remove(                                                            \
+      iceberg::HashMap<K, V, iceberg::DefaultHashBuilder> *map, const K *key, V *out_value);       \
+  const iceberg::KeyValue<K, V> *crust_hashmap_##PREFIX##_next(                                    \
+      const iceberg::HashMap<K, V, iceberg::DefaultHashBuilder> *map, size_t *cursor);             \
+  void crust_hashmap_##PREFIX##_free(iceberg::HashMap<K, V, iceberg::DefaultHashBuilder> *map);    \
+  }                                                                                                \
+  template<>                                                                                       \
+  struct iceberg::HashMapOps<K, V> {                                                               \
+    static constexpr auto init = crust_hashmap_##PREFIX##_init;                                    \
+    static constexpr auto len = crust_hashmap_##PREFIX##_len;                                      \
+    static constexpr auto get = crust_hashmap_##PREFIX##_get;                                      \
+    static constexpr auto contains = crust_hashmap_##PREFIX##_contains;                            \
+    static constexpr auto insert = crust_hashmap_##PREFIX##_insert;                                \
+    static constexpr auto remove = crust_hashmap_##PREFIX##_remove;                                \
+    static constexpr auto next = crust_hashmap_##PREFIX##_next;                                    \
+    static constexpr auto free = crust_hashmap_##PREFIX##_free;                                    \
+  };
+
+/// Declares the function that `export_owned_box!(T, prefix = "PREFIX")`
+/// generates in Rust and makes it the destructor of `iceberg::OwnedBox<T>`.
+/// Use it at global scope, once for every instantiation that is exported.
+#define CRUST_EXPORT_OWNED_BOX(T, PREFIX)                                                          \
+  extern "C" void crust_owned_box_##PREFIX##_drop(iceberg::OwnedBox<T> *owned_box);               \
+  template<>                                                                                       \
+  struct iceberg::OwnedBoxOps<T> {                                                                 \
+    static constexpr auto drop = crust_owned_box_##PREFIX##_drop;                                  \
+  };
+
+CRUST_EXPORT_HASHMAP(iceberg::OwnedStr, int64_t, str_i64)
+CRUST_EXPORT_HASHMAP(iceberg::OwnedStr, iceberg::OwnedStr, str_str)
+CRUST_EXPORT_OWNED_BOX(iceberg::OwnedStr, str)
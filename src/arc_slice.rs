@@ -0,0 +1,340 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+#![allow(unsafe_code)]
+
+//! A replacement for `Arc<[T]>` that cbindgen can understand.
+
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::alloc::{self, Layout};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::atomic::{self, AtomicUsize, Ordering};
+use std::{fmt, iter, process, ptr, slice};
+
+use crate::owned_slice::OwnedSlice;
+
+/// The reference count in front of the elements of an [`ArcSlice`]. It also
+/// records the layout of the allocation, so that it can be released without
+/// knowing the element type.
+pub struct ArcHeader {
+    count: AtomicUsize,
+    size: usize,
+    align: usize,
+}
+
+/// A struct that replaces an `Arc<[T]>`, but which cbindgen can understand.
+///
+/// The header and the elements share one allocation. Cloning only bumps the
+/// reference count, on either side of the FFI boundary: the C++ copy
+/// constructor calls [`crust_arc_slice_retain`], and the destructor calls
+/// [`crust_arc_slice_release`]. Empty slices do not allocate, and their
+/// header is null.
+///
+/// cbindgen:derive-eq=false
+/// cbindgen:derive-neq=false
+#[repr(C)]
+pub struct ArcSlice<T: Sized> {
+    header: *const ArcHeader,
+    ptr: *const T,
+    len: usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Sized> Default for ArcSlice<T> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            header: ptr::null(),
+            ptr: ptr::null(),
+            len: 0,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Sized> Drop for ArcSlice<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            if crust_arc_slice_release(self.header) {
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.ptr as *mut T, self.len));
+                crust_arc_slice_free(self.header);
+            }
+        }
+    }
+}
+
+unsafe impl<T: Sized + Send + Sync> Send for ArcSlice<T> {}
+unsafe impl<T: Sized + Send + Sync> Sync for ArcSlice<T> {}
+
+impl<T: Sized> Clone for ArcSlice<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        unsafe { crust_arc_slice_retain(self.header) };
+        Self {
+            header: self.header,
+            ptr: self.ptr,
+            len: self.len,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArcSlice<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.deref().fmt(formatter)
+    }
+}
+
+impl<T: PartialEq> PartialEq for ArcSlice<T> {
+    /// Compares the elements. Slices that share an allocation are not
+    /// assumed to be equal, since `T` need not be reflexive: `[f64::NAN]` is
+    /// not equal to its clone.
+    fn eq(&self, other: &Self) -> bool {
+        self.deref().eq(other.deref())
+    }
+}
+
+impl<T: Eq> Eq for ArcSlice<T> {}
+
+impl<T: Hash> Hash for ArcSlice<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.deref().hash(state)
+    }
+}
+
+impl<T: Sized> ArcSlice<T> {
+    /// Convert the regular slice into a shared slice.
+    #[inline]
+    pub fn from_slice(s: &[T]) -> Self
+    where
+        T: Clone,
+    {
+        Self::from(s.to_vec())
+    }
+
+    /// Returns the number of `ArcSlice`s that share the elements, or zero if
+    /// the slice is empty and does not allocate.
+    #[inline]
+    pub fn strong_count(&self) -> usize {
+        match unsafe { self.header.as_ref() } {
+            Some(header) => header.count.load(Ordering::Acquire),
+            None => 0,
+        }
+    }
+
+    /// Returns whether both slices share the same allocation.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        ptr::eq(self.header, other.header) && ptr::eq(self.ptr, other.ptr)
+    }
+
+    /// Returns the layout of the allocation and the offset of the elements
+    /// in it.
+    fn layout(len: usize) -> (Layout, usize) {
+        let (layout, offset) = Layout::new::<ArcHeader>()
+            .extend(Layout::array::<T>(len).expect("ArcSlice capacity overflow."))
+            .expect("ArcSlice capacity overflow.");
+        (layout.pad_to_align(), offset)
+    }
+}
+
+/// Increments the reference count behind `header`. Does nothing if `header`
+/// is null.
+///
+/// # Safety
+///
+/// `header` must be null or belong to a live `ArcSlice`.
+#[no_mangle]
+pub unsafe extern "C" fn crust_arc_slice_retain(header: *const ArcHeader) {
+    if let Some(header) = header.as_ref() {
+        if header.count.fetch_add(1, Ordering::Relaxed) > isize::MAX as usize {
+            process::abort();
+        }
+    }
+}
+
+/// Decrements the reference count behind `header` and returns whether that
+/// released the last reference. In that case the caller must drop the
+/// elements and then call [`crust_arc_slice_free`]. Returns false if `header`
+/// is null.
+///
+/// # Safety
+///
+/// `header` must be null or belong to a live `ArcSlice`, whose reference is
+/// given up.
+#[no_mangle]
+pub unsafe extern "C" fn crust_arc_slice_release(header: *const ArcHeader) -> bool {
+    let Some(header) = header.as_ref() else {
+        return false;
+    };
+    if header.count.fetch_sub(1, Ordering::Release) != 1 {
+        return false;
+    }
+    atomic::fence(Ordering::Acquire);
+    true
+}
+
+/// Releases the allocation behind `header` after the last reference was
+/// released and the elements were dropped.
+///
+/// # Safety
+///
+/// [`crust_arc_slice_release`] must have returned true for `header`.
+#[no_mangle]
+pub unsafe extern "C" fn crust_arc_slice_free(header: *const ArcHeader) {
+    let layout = Layout::from_size_align_unchecked((*header).size, (*header).align);
+    alloc::dealloc(header as *mut u8, layout);
+}
+
+impl<T> Deref for ArcSlice<T> {
+    type Target = [T];
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<T> From<Vec<T>> for ArcSlice<T> {
+    #[inline]
+    fn from(mut vec: Vec<T>) -> Self {
+        let len = vec.len();
+        if len == 0 {
+            return Self::default();
+        }
+        let (layout, offset) = Self::layout(len);
+        unsafe {
+            let base = alloc::alloc(layout);
+            if base.is_null() {
+                alloc::handle_alloc_error(layout);
+            }
+            let header = base.cast::<ArcHeader>();
+            header.write(ArcHeader {
+                count: AtomicUsize::new(1),
+                size: layout.size(),
+                align: layout.align(),
+            });
+            let elements = base.add(offset).cast::<T>();
+            ptr::copy_nonoverlapping(vec.as_ptr(), elements, len);
+            vec.set_len(0);
+            Self {
+                header,
+                ptr: elements,
+                len,
+                _phantom: PhantomData,
+            }
+        }
+    }
+}
+
+impl<T> From<OwnedSlice<T>> for ArcSlice<T> {
+    #[inline]
+    fn from(s: OwnedSlice<T>) -> Self {
+        Self::from(s.into_vec())
+    }
+}
+
+impl<T: Clone> From<&[T]> for ArcSlice<T> {
+    #[inline]
+    fn from(s: &[T]) -> Self {
+        Self::from_slice(s)
+    }
+}
+
+impl<T> iter::FromIterator<T> for ArcSlice<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Vec::from_iter(iter).into()
+    }
+}
+
+impl<T: Serialize> Serialize for ArcSlice<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.deref().serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for ArcSlice<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let r = Vec::<T>::deserialize(deserializer)?;
+        Ok(r.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::owned_str::OwnedStr;
+    use std::mem;
+
+    #[test]
+    fn arc_slice() {
+        let record = r#"
+        [1,2,3,4,5]
+        "#;
+
+        let result: ArcSlice<i32> = serde_json::from_str(record).unwrap();
+        assert_eq!(3, result[2]);
+        let result_two: ArcSlice<i32> = serde_json::from_str(
+            &serde_json::to_string(&result).expect("Failed to serialize result"),
+        )
+        .expect("Failed to serialize json");
+        assert_eq!(result, result_two);
+        assert!(!result.ptr_eq(&result_two));
+
+        let empty: ArcSlice<u64> = ArcSlice::from(Vec::new());
+        assert!(empty.is_empty());
+        assert_eq!(empty.strong_count(), 0);
+        assert_eq!(empty.clone(), ArcSlice::default());
+    }
+
+    #[test]
+    fn arc_slice_shares_elements() {
+        let strings: ArcSlice<OwnedStr> = (0..4).map(|i| format!("s{i}").into()).collect();
+        assert_eq!(strings.strong_count(), 1);
+        let copy = strings.clone();
+        assert!(copy.ptr_eq(&strings));
+        assert_eq!(strings.strong_count(), 2);
+
+        let handle = std::thread::spawn(move || copy.iter().map(|s| s.len()).sum::<usize>());
+        assert_eq!(handle.join().unwrap(), 8);
+        assert_eq!(strings.strong_count(), 1);
+
+        // SAFETY: the extra reference is released again, and the count never
+        // reaches zero through the FFI functions.
+        unsafe {
+            crust_arc_slice_retain(strings.header);
+            assert_eq!(strings.strong_count(), 2);
+            assert!(!crust_arc_slice_release(strings.header));
+            assert!(!crust_arc_slice_release(ptr::null()));
+        }
+        assert_eq!(&*strings[3], "s3");
+        assert_eq!(mem::size_of::<ArcSlice<u8>>(), 3 * mem::size_of::<usize>());
+    }
+
+    #[test]
+    fn arc_slice_eq_compares_elements() {
+        let nan: ArcSlice<f64> = ArcSlice::from(vec![f64::NAN]);
+        let copy = nan.clone();
+        assert!(copy.ptr_eq(&nan));
+        assert_ne!(copy, nan);
+
+        let numbers: ArcSlice<f64> = ArcSlice::from(vec![1.0, 2.0]);
+        assert_eq!(numbers.clone(), numbers);
+        assert_eq!(ArcSlice::from(vec![1.0, 2.0]), numbers);
+    }
+}
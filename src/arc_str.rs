@@ -0,0 +1,131 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+#![allow(unsafe_code)]
+
+//! A replacement for `Arc<str>` that has a defined layout for FFI.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::arc_slice::ArcSlice;
+use crate::owned_str::OwnedStr;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Deref;
+
+/// A struct that basically replaces an Arc<str>, but with a defined layout,
+/// suitable for FFI. Cloning it only bumps the reference count.
+#[repr(transparent)]
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct ArcStr(ArcSlice<u8>);
+
+impl fmt::Debug for ArcStr {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.deref().fmt(formatter)
+    }
+}
+
+impl fmt::Display for ArcStr {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.deref().fmt(formatter)
+    }
+}
+
+impl Deref for ArcStr {
+    type Target = str;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        unsafe { std::str::from_utf8_unchecked(&self.0) }
+    }
+}
+
+impl ArcStr {
+    /// Returns the number of `ArcStr`s that share the string, or zero if it
+    /// is empty and does not allocate.
+    #[inline]
+    pub fn strong_count(&self) -> usize {
+        self.0.strong_count()
+    }
+
+    /// Returns whether both strings share the same allocation.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.0.ptr_eq(&other.0)
+    }
+}
+
+impl From<&str> for ArcStr {
+    #[inline]
+    fn from(s: &str) -> Self {
+        ArcStr(s.as_bytes().into())
+    }
+}
+
+impl From<String> for ArcStr {
+    #[inline]
+    fn from(s: String) -> Self {
+        ArcStr(s.into_bytes().into())
+    }
+}
+
+impl From<OwnedStr> for ArcStr {
+    #[inline]
+    fn from(s: OwnedStr) -> Self {
+        ArcStr(s.0.into())
+    }
+}
+
+impl Hash for ArcStr {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state)
+    }
+}
+
+impl Serialize for ArcStr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.deref().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ArcStr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let r = String::deserialize(deserializer)?;
+        Ok(r.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arc_str() {
+        let record = r#"
+        "hello world"
+        "#;
+
+        let result: ArcStr = serde_json::from_str(record).unwrap();
+        assert_eq!("hello world", result.deref());
+        let result_two: ArcStr = serde_json::from_str(
+            &serde_json::to_string(&result).expect("Failed to serialize result"),
+        )
+        .expect("Failed to serialize json");
+        assert_eq!(result, result_two);
+
+        let shared = result.clone();
+        assert!(shared.ptr_eq(&result));
+        assert_eq!(result.strong_count(), 2);
+        assert_eq!(
+            ArcStr::from(OwnedStr::from("hello world".to_string())),
+            shared
+        );
+    }
+}
@@ -0,0 +1,444 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+#![allow(unsafe_code)]
+
+//! Borrowed replacements for `&[T]`, `&mut [T]` and `&str` that cbindgen can
+//! understand.
+
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::str::Utf8Error;
+use std::{fmt, ptr, slice};
+
+use crate::owned_slice::OwnedSlice;
+use crate::owned_str::OwnedStr;
+
+/// A struct that replaces a `&'a [T]`, but which cbindgen can understand.
+///
+/// C++ converts a `std::span` into it implicitly, so a buffer owned by C++ can
+/// be handed to Rust without copying it. The pointer may be null if the slice
+/// is empty, as it is for an empty `std::span`.
+#[repr(C)]
+pub struct Slice<'a, T> {
+    ptr: *const T,
+    len: usize,
+    _phantom: PhantomData<&'a [T]>,
+}
+
+/// A struct that replaces a `&'a mut [T]`, but which cbindgen can
+/// understand.
+///
+/// C++ converts a `std::span` of mutable elements into it implicitly. The
+/// pointer may be null if the slice is empty.
+#[repr(C)]
+pub struct SliceMut<'a, T> {
+    ptr: *mut T,
+    len: usize,
+    _phantom: PhantomData<&'a mut [T]>,
+}
+
+/// A struct that replaces a `&'a str`, but which cbindgen can understand.
+///
+/// C++ creates it from a `std::string_view` with `Str::FromUtf8`, which
+/// checks the bytes with [`crust_str_validate`], or with
+/// `Str::FromUtf8Unchecked` if they are known to be UTF-8. Rust code that
+/// cannot rely on the latter checks the bytes with
+/// [`validate`](Str::validate).
+#[repr(C)]
+pub struct Str<'a> {
+    ptr: *const u8,
+    len: usize,
+    _phantom: PhantomData<&'a str>,
+}
+
+unsafe impl<T: Sync> Send for Slice<'_, T> {}
+unsafe impl<T: Sync> Sync for Slice<'_, T> {}
+unsafe impl<T: Send> Send for SliceMut<'_, T> {}
+unsafe impl<T: Sync> Sync for SliceMut<'_, T> {}
+unsafe impl Send for Str<'_> {}
+unsafe impl Sync for Str<'_> {}
+
+impl<T> Clone for Slice<'_, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Slice<'_, T> {}
+
+impl Clone for Str<'_> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl Copy for Str<'_> {}
+
+impl<T> Default for Slice<'_, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::from(&[][..])
+    }
+}
+
+impl<T> Default for SliceMut<'_, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::from(&mut [][..])
+    }
+}
+
+impl Default for Str<'_> {
+    #[inline]
+    fn default() -> Self {
+        Self::from("")
+    }
+}
+
+impl<'a, T> Slice<'a, T> {
+    /// Returns the borrowed slice with the full lifetime `'a`.
+    #[inline]
+    pub fn as_slice(self) -> &'a [T] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    /// Returns whether both views point at the same memory.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        ptr::eq(self.ptr, other.ptr) && self.len == other.len
+    }
+}
+
+impl<'a, T> SliceMut<'a, T> {
+    /// Turns the view into the mutable slice it borrows.
+    #[inline]
+    pub fn into_slice(self) -> &'a mut [T] {
+        if self.len == 0 {
+            return &mut [];
+        }
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl<'a> Str<'a> {
+    /// Borrows `bytes` as a string if they are valid UTF-8.
+    #[inline]
+    pub fn from_utf8(bytes: &'a [u8]) -> Result<Self, Utf8Error> {
+        std::str::from_utf8(bytes).map(Self::from)
+    }
+
+    /// Borrows `bytes` as a string without checking them.
+    ///
+    /// # Safety
+    ///
+    /// `bytes` must be valid UTF-8.
+    #[inline]
+    pub unsafe fn from_utf8_unchecked(bytes: &'a [u8]) -> Self {
+        Self {
+            ptr: bytes.as_ptr(),
+            len: bytes.len(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the borrowed bytes without assuming that they are UTF-8.
+    #[inline]
+    pub fn as_bytes(self) -> &'a [u8] {
+        Slice {
+            ptr: self.ptr,
+            len: self.len,
+            _phantom: PhantomData,
+        }
+        .as_slice()
+    }
+
+    /// Returns the string if its bytes are valid UTF-8. Use it on strings
+    /// received from C++ that may not be.
+    #[inline]
+    pub fn validate(self) -> Result<Self, Utf8Error> {
+        Self::from_utf8(self.as_bytes())
+    }
+
+    /// Returns the borrowed string with the full lifetime `'a`.
+    #[inline]
+    pub fn as_str(self) -> &'a str {
+        unsafe { std::str::from_utf8_unchecked(self.as_bytes()) }
+    }
+}
+
+/// Returns whether the `len` bytes at `ptr` are valid UTF-8. C++ calls it in
+/// `Str::FromUtf8`.
+///
+/// # Safety
+///
+/// `ptr` must be valid for reads of `len` bytes, or `len` must be zero.
+#[no_mangle]
+pub unsafe extern "C" fn crust_str_validate(ptr: *const u8, len: usize) -> bool {
+    let bytes = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, len)
+    };
+    std::str::from_utf8(bytes).is_ok()
+}
+
+impl<T> Deref for Slice<'_, T> {
+    type Target = [T];
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl<T> Deref for SliceMut<'_, T> {
+    type Target = [T];
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<T> DerefMut for SliceMut<'_, T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        if self.len == 0 {
+            return &mut [];
+        }
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Deref for Str<'_> {
+    type Target = str;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl<'a, T> From<&'a [T]> for Slice<'a, T> {
+    #[inline]
+    fn from(s: &'a [T]) -> Self {
+        Self {
+            ptr: s.as_ptr(),
+            len: s.len(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> From<&'a mut [T]> for SliceMut<'a, T> {
+    #[inline]
+    fn from(s: &'a mut [T]) -> Self {
+        Self {
+            ptr: s.as_mut_ptr(),
+            len: s.len(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> From<SliceMut<'a, T>> for Slice<'a, T> {
+    #[inline]
+    fn from(s: SliceMut<'a, T>) -> Self {
+        Self::from(&*s.into_slice())
+    }
+}
+
+impl<'a> From<&'a str> for Str<'a> {
+    #[inline]
+    fn from(s: &'a str) -> Self {
+        unsafe { Self::from_utf8_unchecked(s.as_bytes()) }
+    }
+}
+
+impl<T: Clone> From<Slice<'_, T>> for OwnedSlice<T> {
+    #[inline]
+    fn from(s: Slice<'_, T>) -> Self {
+        Self::from_slice(&s)
+    }
+}
+
+impl From<Str<'_>> for OwnedStr {
+    #[inline]
+    fn from(s: Str<'_>) -> Self {
+        Self::from(s.as_str().to_owned())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Slice<'_, T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.deref().fmt(formatter)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SliceMut<'_, T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.deref().fmt(formatter)
+    }
+}
+
+impl fmt::Debug for Str<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.deref().fmt(formatter)
+    }
+}
+
+impl fmt::Display for Str<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.deref().fmt(formatter)
+    }
+}
+
+impl<T: PartialEq> PartialEq for Slice<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref().eq(other.deref())
+    }
+}
+
+impl<T: Eq> Eq for Slice<'_, T> {}
+
+impl<T: PartialEq> PartialEq for SliceMut<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref().eq(other.deref())
+    }
+}
+
+impl<T: Eq> Eq for SliceMut<'_, T> {}
+
+impl PartialEq for Str<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref().eq(other.deref())
+    }
+}
+
+impl Eq for Str<'_> {}
+
+impl<T: Hash> Hash for Slice<'_, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.deref().hash(state)
+    }
+}
+
+/// Hashes like [`OwnedStr`], so both can be looked up in the same way.
+impl Hash for Str<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state)
+    }
+}
+
+impl<T: Serialize> Serialize for Slice<'_, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.deref().serialize(serializer)
+    }
+}
+
+impl<T: Serialize> Serialize for SliceMut<'_, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.deref().serialize(serializer)
+    }
+}
+
+impl Serialize for Str<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.deref().serialize(serializer)
+    }
+}
+
+/// Borrows from the input, so it only works for strings that need no
+/// unescaping.
+impl<'de: 'a, 'a> Deserialize<'de> for Str<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let r = <&'a str>::deserialize(deserializer)?;
+        Ok(r.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn borrowed_slice() {
+        let values = vec![1, 2, 3];
+        let view = Slice::from(&values[..]);
+        assert_eq!(&*view, &[1, 2, 3]);
+        assert!(view.ptr_eq(&Slice::from(&values[..])));
+        assert_eq!(OwnedSlice::from(view), OwnedSlice::from(values.clone()));
+
+        let mut values = values;
+        let mut view = SliceMut::from(&mut values[..]);
+        view[0] = 10;
+        view.reverse();
+        assert_eq!(Slice::from(view), Slice::from(&[3, 2, 10][..]));
+        assert_eq!(values, [3, 2, 10]);
+
+        let empty: Slice<'_, i32> = Slice {
+            ptr: ptr::null(),
+            len: 0,
+            _phantom: PhantomData,
+        };
+        assert!(empty.is_empty());
+        assert_eq!(empty, Slice::default());
+        let empty: SliceMut<'_, i32> = SliceMut {
+            ptr: ptr::null_mut(),
+            len: 0,
+            _phantom: PhantomData,
+        };
+        assert!(empty.into_slice().is_empty());
+    }
+
+    #[test]
+    fn borrowed_str() {
+        let s = Str::from_utf8(b"hello world").unwrap();
+        assert_eq!(&*s, "hello world");
+        assert_eq!(s.to_uppercase(), "HELLO WORLD");
+        assert!(Str::from_utf8(b"\xff").is_err());
+        assert!(Str::from_utf8(b"\xc3").is_err());
+        assert_eq!(s.validate(), Ok(s));
+
+        // SAFETY: the pointers are valid for the given lengths.
+        unsafe {
+            assert!(crust_str_validate(s.as_ptr(), s.len()));
+            assert!(!crust_str_validate(b"\xc3".as_ptr(), 1));
+            assert!(crust_str_validate(ptr::null(), 0));
+        }
+
+        let json = serde_json::to_string(&s).unwrap();
+        let s_two: Str<'_> = serde_json::from_str(&json).unwrap();
+        assert_eq!(s, s_two);
+        assert_eq!(
+            OwnedStr::from(s_two),
+            OwnedStr::from("hello world".to_string())
+        );
+        assert!(Str::default().is_empty());
+    }
+}
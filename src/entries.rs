@@ -0,0 +1,228 @@
+//! Iterators over the entries of the maps that keep them in a slice of
+//! [`KeyValue`]s, in order: [`IndexMap`](crate::indexmap::IndexMap) and
+//! [`SortedMap`](crate::sortedmap::SortedMap).
+
+use std::iter::FusedIterator;
+use std::{slice, vec};
+
+use crate::hashmap::KeyValue;
+
+/// An iterator over the entries of an [`IndexMap`](crate::indexmap::IndexMap)
+/// or a [`SortedMap`](crate::sortedmap::SortedMap), in order.
+///
+/// This struct is created by the [`iter`](crate::indexmap::IndexMap::iter)
+/// method on [`IndexMap`](crate::indexmap::IndexMap), and by the `iter` and
+/// `range` methods on [`SortedMap`](crate::sortedmap::SortedMap).
+pub struct Iter<'a, K, V> {
+    pub(crate) inner: slice::Iter<'a, KeyValue<K, V>>,
+}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Iter {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> std::option::Option<Self::Item> {
+        self.inner.next().map(|entry| (&entry.key, &entry.value))
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    fn next_back(&mut self) -> std::option::Option<Self::Item> {
+        self.inner
+            .next_back()
+            .map(|entry| (&entry.key, &entry.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+/// A mutable iterator over the entries of an
+/// [`IndexMap`](crate::indexmap::IndexMap) or a
+/// [`SortedMap`](crate::sortedmap::SortedMap), in order.
+///
+/// This struct is created by the `iter_mut` methods on
+/// [`IndexMap`](crate::indexmap::IndexMap) and
+/// [`SortedMap`](crate::sortedmap::SortedMap), and by the `range_mut` method on
+/// [`SortedMap`](crate::sortedmap::SortedMap).
+pub struct IterMut<'a, K, V> {
+    pub(crate) inner: slice::IterMut<'a, KeyValue<K, V>>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> std::option::Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|entry| (&entry.key, &mut entry.value))
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IterMut<'_, K, V> {
+    fn next_back(&mut self) -> std::option::Option<Self::Item> {
+        self.inner
+            .next_back()
+            .map(|entry| (&entry.key, &mut entry.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+/// An owning iterator over the entries of an
+/// [`IndexMap`](crate::indexmap::IndexMap) or a
+/// [`SortedMap`](crate::sortedmap::SortedMap), in order.
+///
+/// This struct is created by the `into_iter` methods on
+/// [`IndexMap`](crate::indexmap::IndexMap) and
+/// [`SortedMap`](crate::sortedmap::SortedMap).
+pub struct IntoIter<K, V> {
+    pub(crate) inner: vec::IntoIter<KeyValue<K, V>>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> std::option::Option<Self::Item> {
+        self.inner.next().map(|entry| (entry.key, entry.value))
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> std::option::Option<Self::Item> {
+        self.inner.next_back().map(|entry| (entry.key, entry.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+/// An iterator over the keys of an [`IndexMap`](crate::indexmap::IndexMap) or a
+/// [`SortedMap`](crate::sortedmap::SortedMap), in order.
+///
+/// This struct is created by the `keys` methods on
+/// [`IndexMap`](crate::indexmap::IndexMap) and
+/// [`SortedMap`](crate::sortedmap::SortedMap).
+pub struct Keys<'a, K, V> {
+    pub(crate) inner: slice::Iter<'a, KeyValue<K, V>>,
+}
+
+impl<K, V> Clone for Keys<'_, K, V> {
+    fn clone(&self) -> Self {
+        Keys {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> std::option::Option<&'a K> {
+        self.inner.next().map(|entry| &entry.key)
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Keys<'_, K, V> {
+    fn next_back(&mut self) -> std::option::Option<Self::Item> {
+        self.inner.next_back().map(|entry| &entry.key)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+impl<K, V> FusedIterator for Keys<'_, K, V> {}
+
+/// An iterator over the values of an [`IndexMap`](crate::indexmap::IndexMap) or
+/// a [`SortedMap`](crate::sortedmap::SortedMap), in order.
+///
+/// This struct is created by the `values` methods on
+/// [`IndexMap`](crate::indexmap::IndexMap) and
+/// [`SortedMap`](crate::sortedmap::SortedMap).
+pub struct Values<'a, K, V> {
+    pub(crate) inner: slice::Iter<'a, KeyValue<K, V>>,
+}
+
+impl<K, V> Clone for Values<'_, K, V> {
+    fn clone(&self) -> Self {
+        Values {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> std::option::Option<&'a V> {
+        self.inner.next().map(|entry| &entry.value)
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Values<'_, K, V> {
+    fn next_back(&mut self) -> std::option::Option<Self::Item> {
+        self.inner.next_back().map(|entry| &entry.value)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+impl<K, V> FusedIterator for Values<'_, K, V> {}
+
+/// A mutable iterator over the values of an
+/// [`IndexMap`](crate::indexmap::IndexMap) or a
+/// [`SortedMap`](crate::sortedmap::SortedMap), in order.
+///
+/// This struct is created by the `values_mut` methods on
+/// [`IndexMap`](crate::indexmap::IndexMap) and
+/// [`SortedMap`](crate::sortedmap::SortedMap).
+pub struct ValuesMut<'a, K, V> {
+    pub(crate) inner: slice::IterMut<'a, KeyValue<K, V>>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> std::option::Option<&'a mut V> {
+        self.inner.next().map(|entry| &mut entry.value)
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for ValuesMut<'_, K, V> {
+    fn next_back(&mut self) -> std::option::Option<Self::Item> {
+        self.inner.next_back().map(|entry| &mut entry.value)
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+impl<K, V> FusedIterator for ValuesMut<'_, K, V> {}
@@ -0,0 +1,142 @@
+//! A stable implementation of the Fx hash function.
+//!
+//! `std::collections::hash_map::DefaultHasher` does not specify its
+//! algorithm, so hashes computed by one toolchain can differ from hashes
+//! computed by another. Maps that are shared with C++ or persisted need a
+//! hash function that is fixed, which is what this module provides.
+//!
+//! The algorithm is the one used by rustc (`FxHasher`), pinned to a 64-bit
+//! state and little-endian reads so that it produces the same output on
+//! every platform:
+//!
+//! ```text
+//! hash = 0
+//! for each word:
+//!     hash = (rotate_left(hash, 5) ^ word) * 0x517cc1b727220a95
+//! finish:
+//!     rotate_left(hash, 26)
+//! ```
+//!
+//! The multiplication only moves entropy towards the high bits, so without
+//! the final rotation keys that differ only in their high bits, such as
+//! aligned integers, would share the low bits of their hashes, and with them
+//! their home slot in the table. The rotation is the one of rustc-hash 2.
+//!
+//! Byte slices are consumed in chunks of 8, 4, 2 and 1 bytes, each read as
+//! a little-endian integer. `usize` and `isize` are always hashed as 64-bit
+//! values.
+
+use std::hash::{BuildHasher, Hasher};
+
+/// The multiplier of the Fx hash function.
+pub const CRUST_FXHASH_SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+/// Identifies the hash function used by `HashMap` by default. It is bumped
+/// whenever the algorithm of this module changes, so both sides of the FFI
+/// boundary can check that they agree.
+pub const CRUST_HASHER_ID: u32 = 2;
+
+/// A stable, platform independent version of rustc's `FxHasher`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FxHasher {
+    hash: u64,
+}
+
+impl FxHasher {
+    #[inline]
+    fn add_to_hash(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(CRUST_FXHASH_SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    #[inline]
+    fn write(&mut self, mut bytes: &[u8]) {
+        while let Some((chunk, rest)) = bytes.split_first_chunk::<8>() {
+            self.add_to_hash(u64::from_le_bytes(*chunk));
+            bytes = rest;
+        }
+        if let Some((chunk, rest)) = bytes.split_first_chunk::<4>() {
+            self.add_to_hash(u32::from_le_bytes(*chunk) as u64);
+            bytes = rest;
+        }
+        if let Some((chunk, rest)) = bytes.split_first_chunk::<2>() {
+            self.add_to_hash(u16::from_le_bytes(*chunk) as u64);
+            bytes = rest;
+        }
+        if let Some(&byte) = bytes.first() {
+            self.add_to_hash(byte as u64);
+        }
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.add_to_hash(i);
+    }
+
+    #[inline]
+    fn write_u128(&mut self, i: u128) {
+        self.add_to_hash(i as u64);
+        self.add_to_hash((i >> 64) as u64);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.add_to_hash(i as u64);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.hash.rotate_left(26)
+    }
+}
+
+/// Builds [`FxHasher`]s. This is the default hasher of
+/// [`crate::hashmap::HashMap`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FxBuildHasher;
+
+impl BuildHasher for FxBuildHasher {
+    type Hasher = FxHasher;
+
+    #[inline]
+    fn build_hasher(&self) -> FxHasher {
+        FxHasher::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::BuildHasher;
+
+    use crate::owned_str::OwnedStr;
+
+    use super::FxBuildHasher;
+
+    #[test]
+    fn fxhash_stable() {
+        // These values are part of the FFI contract and must never change
+        // without bumping `CRUST_HASHER_ID`.
+        assert_eq!(FxBuildHasher.hash_one(0u64), 0);
+        assert_eq!(FxBuildHasher.hash_one(1u64), 0xdc9c882a5545f306);
+        assert_eq!(FxBuildHasher.hash_one(1usize), FxBuildHasher.hash_one(1u64));
+        let key: OwnedStr = "hello world".to_string().into();
+        assert_eq!(FxBuildHasher.hash_one(&key), FxBuildHasher.hash_one(&key));
+        assert_eq!(FxBuildHasher.hash_one(&key), 0x7b48090990ae6da3);
+    }
+}
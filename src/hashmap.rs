@@ -1,12 +1,29 @@
 use serde::de::{MapAccess, Visitor};
 use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
 use std::borrow::Borrow;
-use std::hash::{Hash, Hasher};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::iter::FusedIterator;
 use std::marker::PhantomData;
-use std::{collections::hash_map::DefaultHasher, slice::Iter};
+use std::ops::Index;
 use std::{fmt, mem};
+use std::{slice, vec};
 
+mod ffi;
+mod group;
+pub mod robin_hood;
+
+use crate::fxhash::FxBuildHasher;
 use crate::{option::Option, owned_slice::OwnedSlice, owned_str::OwnedStr};
+use group::{Group, DELETED, EMPTY};
+pub use robin_hood::RobinHoodMap;
+
+/// The hasher used by [`HashMap`] unless another one is chosen.
+///
+/// It is a stable, documented hash function (see [`crate::fxhash`]), so the
+/// placement of entries is the same for every toolchain and on both sides of
+/// the FFI boundary. Its identity is exported as
+/// [`CRUST_HASHER_ID`](crate::fxhash::CRUST_HASHER_ID).
+pub type DefaultHashBuilder = FxBuildHasher;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[repr(C)]
@@ -15,116 +32,1316 @@ pub struct KeyValue<K, V> {
     pub value: V,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// A hash map with a layout that cbindgen can describe.
+///
+/// The table follows the SwissTable design: next to the slots there is an
+/// array of control bytes, one per slot, that holds a 7-bit tag of the hash
+/// of every entry. Lookups scan the control bytes a group at a time (with
+/// SSE2 where available) and only compare keys whose tag matches. The slots
+/// themselves stay `Option`s, so the entries can be walked and dropped from
+/// C++ without looking at the control bytes.
+///
+/// The hasher `S` is a type-level choice and is not stored in the map: every
+/// hash is computed with a hasher built from `S::default()`. This keeps the
+/// `#[repr(C)]` layout independent of the hasher, but it also means that `S`
+/// must build identical hashers every time. Randomly seeded builders such as
+/// `std::collections::hash_map::RandomState` must not be used. For the same
+/// reason there are no constructors that take a hash builder: a map with
+/// another hasher is created with [`Default`] and grown with
+/// [`reserve`](HashMap::reserve).
+///
+/// Two maps are equal if they hold the same entries, regardless of
+/// insertion order, capacity or load factor.
+///
+/// `Debug` prints the entries like a map. Use
+/// [`debug_slots`](HashMap::debug_slots) to inspect the table itself.
+#[derive(Clone)]
 #[repr(C)]
-pub struct HashMap<K, V> {
+pub struct HashMap<K, V, S = DefaultHashBuilder> {
+    // The number of slots is zero or a power of two.
     data: OwnedSlice<Option<KeyValue<K, V>>>,
+    // One control byte per slot: `EMPTY`, `DELETED` or the H2 tag of the
+    // hash of the entry. The first `Group::WIDTH` bytes are mirrored after
+    // the last slot, so a group can be loaded at any slot without wrapping.
+    // Empty if there are no slots.
+    ctrl: OwnedSlice<u8>,
     n_items: usize,
-    capacity: usize,
+    // The number of entries that can still be put into `EMPTY` slots before
+    // the table has to be rebuilt.
+    growth_left: usize,
+    // The number of slots minus one, or zero if there are no slots. Probing
+    // for a hash starts at `hash & bucket_mask`.
+    bucket_mask: usize,
+    // The maximum load factor in units of `1 / LOAD_FACTOR_SCALE`.
+    max_load: usize,
+    _hasher: PhantomData<S>,
+}
+
+/// Allocates `buckets` empty slots without requiring the entries to be
+/// `Clone`.
+fn empty_slots<T>(buckets: usize) -> OwnedSlice<Option<T>> {
+    (0..buckets).map(|_| Option::None).collect()
+}
+
+/// Allocates the control bytes for `buckets` empty slots.
+fn empty_ctrl(buckets: usize) -> OwnedSlice<u8> {
+    if buckets == 0 {
+        return OwnedSlice::default();
+    }
+    vec![EMPTY; buckets + Group::WIDTH].into()
+}
+
+/// Allocates `buckets` empty slots, reporting allocation failure instead of
+/// aborting.
+fn try_empty_slots<T>(buckets: usize) -> Result<OwnedSlice<Option<T>>, TryReserveError> {
+    let mut slots = Vec::new();
+    slots.try_reserve_exact(buckets)?;
+    slots.extend((0..buckets).map(|_| Option::None));
+    Ok(slots.into())
+}
+
+/// Allocates the control bytes for `buckets` empty slots, reporting
+/// allocation failure instead of aborting.
+fn try_empty_ctrl(buckets: usize) -> Result<OwnedSlice<u8>, TryReserveError> {
+    if buckets == 0 {
+        return Ok(OwnedSlice::default());
+    }
+    let mut ctrl = Vec::new();
+    ctrl.try_reserve_exact(buckets + Group::WIDTH)?;
+    ctrl.resize(buckets + Group::WIDTH, EMPTY);
+    Ok(ctrl.into())
+}
+
+/// Recounts the entries of the map when dropped during a panic. A rebuild
+/// that a panicking `Hash` cut short drops the entries it had not moved yet,
+/// so the counts have to follow the entries that are left.
+struct RecountOnUnwind<'a, K, V, S>(&'a mut HashMap<K, V, S>);
+
+impl<K, V, S> Drop for RecountOnUnwind<'_, K, V, S> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            let map = &mut *self.0;
+            map.n_items = map.data.iter().filter(|slot| slot.is_some()).count();
+            map.growth_left = bucket_capacity(map.data.len(), map.max_load) - map.n_items;
+        }
+    }
+}
+
+/// Load factors are stored as fixed-point fractions of this value.
+const LOAD_FACTOR_SCALE: usize = 1024;
+
+/// The default maximum load factor of 3/4.
+pub(crate) const DEFAULT_MAX_LOAD: usize = LOAD_FACTOR_SCALE / 4 * 3;
+
+/// Converts a maximum load factor to its fixed-point representation.
+///
+/// # Panics
+///
+/// Panics if the load factor is not strictly between 0 and 1.
+fn max_load_from_f64(max_load_factor: f64) -> usize {
+    assert!(
+        max_load_factor > 0.0 && max_load_factor < 1.0,
+        "Load factor must be between 0 and 1."
+    );
+    ((max_load_factor * LOAD_FACTOR_SCALE as f64) as usize).clamp(1, LOAD_FACTOR_SCALE - 1)
+}
+
+/// Returns the number of entries that fit into `buckets` slots at a maximum
+/// load factor of `max_load / LOAD_FACTOR_SCALE`.
+///
+/// At least one slot always stays `EMPTY`, so that probing terminates.
+pub(crate) fn bucket_capacity(buckets: usize, max_load: usize) -> usize {
+    let capacity = buckets / LOAD_FACTOR_SCALE * max_load
+        + buckets % LOAD_FACTOR_SCALE * max_load / LOAD_FACTOR_SCALE;
+    capacity.min(buckets.saturating_sub(1))
 }
 
-#[inline]
-fn hash<K: Hash>(s: &K) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    s.hash(&mut hasher);
-    hasher.finish()
+/// Returns the number of slots needed to hold `capacity` entries without
+/// exceeding the maximum load factor, or `None` if it overflows.
+pub(crate) fn capacity_to_buckets(capacity: usize, max_load: usize) -> std::option::Option<usize> {
+    if capacity == 0 {
+        return Some(0);
+    }
+    capacity
+        .checked_mul(LOAD_FACTOR_SCALE)
+        .map(|adjusted| adjusted.div_ceil(max_load).max(4))
+        .and_then(usize::checked_next_power_of_two)
+}
+
+/// Returns the capacity to preallocate for a deserializer's `size_hint`.
+///
+/// The hint comes from the input, so it is capped: a huge hint must not make
+/// the allocation fail before a single entry has been read.
+pub(crate) fn cautious_capacity(size_hint: std::option::Option<usize>) -> usize {
+    size_hint.unwrap_or(0).min(4096)
+}
+
+/// The error type for [`HashMap::try_reserve`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TryReserveError {
+    /// The number of slots needed overflows `usize`.
+    CapacityOverflow,
+    /// Allocating the table failed, or its size would exceed `isize::MAX`
+    /// bytes.
+    AllocError(std::collections::TryReserveError),
+}
+
+impl From<std::collections::TryReserveError> for TryReserveError {
+    fn from(err: std::collections::TryReserveError) -> Self {
+        TryReserveError::AllocError(err)
+    }
+}
+
+impl fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryReserveError::CapacityOverflow => f.write_str("Hash table capacity overflow."),
+            TryReserveError::AllocError(err) => write!(f, "Hash table allocation failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for TryReserveError {
+    fn source(&self) -> std::option::Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TryReserveError::CapacityOverflow => None,
+            TryReserveError::AllocError(err) => Some(err),
+        }
+    }
 }
 
 #[no_mangle]
 pub extern "C" fn crust_hash_owned_str(s: &OwnedStr) -> u64 {
-    hash(s)
+    DefaultHashBuilder::default().hash_one(s)
 }
 
-impl<K: Clone + Hash + Eq, V: Clone> HashMap<K, V> {
+impl<K, V> HashMap<K, V> {
+    /// Creates an empty map. It does not allocate until the first insert.
     pub fn new() -> Self {
-        let capacity = 64;
-        Self::with_capacity(capacity)
+        Self::with_capacity(0)
     }
 
+    /// Creates an empty map that can hold at least `capacity` entries
+    /// without reallocating.
     pub fn with_capacity(capacity: usize) -> Self {
-        let data = OwnedSlice::from(vec![Option::None; capacity]);
+        Self::with_capacity_and_max_load(capacity, DEFAULT_MAX_LOAD)
+    }
+
+    /// Creates an empty map that can hold at least `capacity` entries
+    /// without reallocating and that grows once more than `max_load_factor`
+    /// of its slots are filled. The default is 3/4.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_load_factor` is not strictly between 0 and 1.
+    pub fn with_capacity_and_max_load_factor(capacity: usize, max_load_factor: f64) -> Self {
+        Self::with_capacity_and_max_load(capacity, max_load_from_f64(max_load_factor))
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> HashMap<K, V, S> {
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, `None` is returned. If the
+    /// map did have this key present, the value is updated and the old value
+    /// is returned. The key is not updated.
+    pub fn insert(&mut self, key: K, value: V) -> std::option::Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    /// Gets the given key's corresponding entry in the map for in-place
+    /// manipulation.
+    ///
+    /// The key is hashed once, and the table is grown up front if a new entry
+    /// would exceed the load factor.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        self.reserve(1);
+        let hash = self.hash(&key);
+        match self.probe(hash, &key) {
+            Ok(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            Err(index) => Entry::Vacant(VacantEntry {
+                hash,
+                map: self,
+                key,
+                index,
+            }),
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    pub fn get<Q>(&self, key: &Q) -> std::option::Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        Some(&self.entry_at(index).value)
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    pub fn get_key_value<Q>(&self, key: &Q) -> std::option::Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        let entry = self.entry_at(index);
+        Some((&entry.key, &entry.value))
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> std::option::Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        Some(&mut self.entry_at_mut(index).value)
+    }
+
+    /// Attempts to get mutable references to `N` values in the map at once.
+    ///
+    /// Returns `None` if any of the keys is missing, or if two keys refer to
+    /// the same entry.
+    pub fn get_many_mut<Q, const N: usize>(
+        &mut self,
+        keys: [&Q; N],
+    ) -> std::option::Option<[&mut V; N]>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut indices = [0; N];
+        for (index, key) in indices.iter_mut().zip(keys) {
+            *index = self.find(key)?;
+        }
+        let slots = self.data.get_disjoint_mut(indices).ok()?;
+        Some(slots.map(|slot| match slot {
+            Option::Some(entry) => &mut entry.value,
+            Option::None => unreachable!("Slot should be occupied."),
+        }))
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    pub fn remove<Q>(&mut self, key: &Q) -> std::option::Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> std::option::Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        let entry = self.remove_slot(index);
+        Some((entry.key, entry.value))
+    }
+
+    /// Retains only the elements specified by the predicate.
+    ///
+    /// In other words, removes all pairs `(k, v)` for which `f(&k, &mut v)`
+    /// returns `false`.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        for index in 0..self.data.len() {
+            if let Option::Some(entry) = &mut self.data[index] {
+                if !f(&entry.key, &mut entry.value) {
+                    self.remove_slot(index);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        S::default().hash_one(key)
+    }
+
+    /// Returns the index of the slot holding `key`.
+    #[inline]
+    fn find<Q>(&self, key: &Q) -> std::option::Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.n_items == 0 {
+            return None;
+        }
+        self.probe(self.hash(key), key).ok()
+    }
+
+    /// Looks up the slot of `key` in a table that has slots.
+    ///
+    /// The probe sequence visits groups of control bytes in triangular
+    /// steps, starting at `hash & bucket_mask`. Within a group, only the
+    /// slots whose control byte equals the H2 tag of `hash` are compared. The
+    /// search ends at the first group with an `EMPTY` byte; the load factor
+    /// guarantees that there is one.
+    ///
+    /// Returns `Ok` with the index of the slot holding the key, or `Err` with
+    /// the first `EMPTY` or `DELETED` slot of the probe sequence.
+    fn probe<Q>(&self, hash: u64, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let tag = group::h2(hash);
+        let mut insert_slot = None;
+        let mut pos = group::h1(hash, self.bucket_mask);
+        let mut stride = 0;
+        loop {
+            let group = Group::load(&self.ctrl[pos..]);
+            for bit in group.match_byte(tag) {
+                let index = (pos + bit) & self.bucket_mask;
+                if let Option::Some(entry) = &self.data[index] {
+                    if entry.key.borrow() == key {
+                        return Ok(index);
+                    }
+                }
+            }
+            if insert_slot.is_none() {
+                insert_slot = group
+                    .match_empty_or_deleted()
+                    .lowest_set_bit()
+                    .map(|bit| (pos + bit) & self.bucket_mask);
+            }
+            if group.match_empty().any_bit_set() {
+                let index = insert_slot.unwrap_or_else(|| unreachable!("Group has an empty slot."));
+                return Err(self.fix_insert_slot(index));
+            }
+            stride += Group::WIDTH;
+            pos = (pos + stride) & self.bucket_mask;
+        }
+    }
+
+    /// Returns the first `EMPTY` or `DELETED` slot of the probe sequence of
+    /// `hash`.
+    fn find_insert_slot(&self, hash: u64) -> usize {
+        let mut pos = group::h1(hash, self.bucket_mask);
+        let mut stride = 0;
+        loop {
+            let group = Group::load(&self.ctrl[pos..]);
+            if let Some(bit) = group.match_empty_or_deleted().lowest_set_bit() {
+                return self.fix_insert_slot((pos + bit) & self.bucket_mask);
+            }
+            stride += Group::WIDTH;
+            pos = (pos + stride) & self.bucket_mask;
+        }
+    }
+
+    /// In tables with fewer slots than `Group::WIDTH`, a group also contains
+    /// `EMPTY` bytes past the last slot, which map back onto slots that may
+    /// be occupied. In that case the first free slot of the table is used.
+    #[inline]
+    fn fix_insert_slot(&self, index: usize) -> usize {
+        if group::is_full(self.ctrl[index]) {
+            Group::load(&self.ctrl)
+                .match_empty_or_deleted()
+                .lowest_set_bit()
+                .unwrap_or_else(|| unreachable!("Table should have a free slot."))
+        } else {
+            index
+        }
+    }
+
+    #[inline]
+    fn entry_at(&self, index: usize) -> &KeyValue<K, V> {
+        self.data[index]
+            .as_ref()
+            .unwrap_or_else(|| unreachable!("Slot should be occupied."))
+    }
+
+    #[inline]
+    fn entry_at_mut(&mut self, index: usize) -> &mut KeyValue<K, V> {
+        self.data[index]
+            .as_mut()
+            .unwrap_or_else(|| unreachable!("Slot should be occupied."))
+    }
+
+    /// Sets the control byte of a slot and its mirror.
+    #[inline]
+    fn set_ctrl(&mut self, index: usize, ctrl: u8) {
+        let mirror = (index.wrapping_sub(Group::WIDTH) & self.bucket_mask) + Group::WIDTH;
+        self.ctrl[index] = ctrl;
+        self.ctrl[mirror] = ctrl;
+    }
+
+    /// Puts an entry with the given hash into the free slot at `index`.
+    fn insert_slot(&mut self, index: usize, hash: u64, entry: KeyValue<K, V>) {
+        if self.ctrl[index] == EMPTY {
+            self.growth_left -= 1;
+        }
+        self.set_ctrl(index, group::h2(hash));
+        self.data[index] = Option::Some(entry);
+        self.n_items += 1;
+    }
+
+    /// Takes the entry out of the slot at `index`.
+    ///
+    /// The slot becomes `EMPTY` again unless it sits inside a run of
+    /// `Group::WIDTH` slots without an `EMPTY` one. A probe sequence may have
+    /// passed over such a run, so the slot is marked `DELETED` instead to
+    /// keep later entries reachable. `DELETED` slots are reused by inserts
+    /// and cleared when the table is rebuilt.
+    fn remove_slot(&mut self, index: usize) -> KeyValue<K, V> {
+        let entry = mem::replace(&mut self.data[index], Option::None)
+            .unwrap_or_else(|| unreachable!("Removed slot should be occupied."));
+        self.n_items -= 1;
+        let index_before = index.wrapping_sub(Group::WIDTH) & self.bucket_mask;
+        let empty_before = Group::load(&self.ctrl[index_before..]).match_empty();
+        let empty_after = Group::load(&self.ctrl[index..]).match_empty();
+        let ctrl = if empty_before.leading_zeros() + empty_after.trailing_zeros() >= Group::WIDTH {
+            DELETED
+        } else {
+            self.growth_left += 1;
+            EMPTY
+        };
+        self.set_ctrl(index, ctrl);
+        entry
+    }
+
+    /// Returns a diagnostic view of the table for debugging.
+    ///
+    /// Its `Debug` output lists every slot, including empty and deleted
+    /// ones, with the tag and the probe length of each entry. The probe
+    /// length is the number of groups a lookup loads before it reaches the
+    /// entry, so `1` means the entry sits in its home group.
+    pub fn debug_slots(&self) -> DebugSlots<'_, K, V, S> {
+        DebugSlots { map: self }
+    }
+
+    /// Returns the number of groups the probe sequence of `hash` loads up to
+    /// and including the one that contains the slot `index`.
+    fn probe_len(&self, hash: u64, index: usize) -> usize {
+        let mut pos = group::h1(hash, self.bucket_mask);
+        let mut stride = 0;
+        let mut groups = 1;
+        while (index.wrapping_sub(pos) & self.bucket_mask) >= Group::WIDTH {
+            stride += Group::WIDTH;
+            pos = (pos + stride) & self.bucket_mask;
+            groups += 1;
+        }
+        groups
+    }
+
+    /// Reserves capacity for at least `additional` more entries to be
+    /// inserted without reallocating.
+    ///
+    /// If the table is mostly filled with removed entries, it is rebuilt at
+    /// the same size instead of growing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new number of slots overflows `usize`.
+    pub fn reserve(&mut self, additional: usize) {
+        if additional <= self.growth_left {
+            return;
+        }
+        let buckets = self
+            .reserve_buckets(additional)
+            .expect("Hash table capacity overflow.");
+        self.resize(buckets);
+    }
+
+    /// Tries to reserve capacity for at least `additional` more entries to be
+    /// inserted without reallocating.
+    ///
+    /// Unlike [`reserve`](HashMap::reserve), this returns an error instead of
+    /// panicking or aborting if the capacity overflows or the allocation
+    /// fails. The map is unchanged in that case.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if additional <= self.growth_left {
+            return Ok(());
+        }
+        let buckets = self
+            .reserve_buckets(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        let data = try_empty_slots(buckets)?;
+        let ctrl = try_empty_ctrl(buckets)?;
+        self.rebuild(data, ctrl);
+        Ok(())
+    }
+
+    /// Shrinks the capacity of the map as much as possible while keeping the
+    /// load factor. An empty map releases its allocation.
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    /// Shrinks the capacity of the map with a lower limit. The capacity
+    /// stays at least as large as both the length and `min_capacity`.
+    ///
+    /// If the current capacity is less than the lower limit, this is a no-op.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let buckets = capacity_to_buckets(self.n_items.max(min_capacity), self.max_load)
+            .unwrap_or(usize::MAX);
+        if buckets < self.data.len() {
+            self.resize(buckets);
+        }
+    }
+
+    /// Returns the number of slots to rebuild the table with so that
+    /// `additional` more entries fit, or `None` if it overflows.
+    fn reserve_buckets(&self, additional: usize) -> std::option::Option<usize> {
+        let needed = self.n_items.checked_add(additional)?;
+        let full_capacity = self.capacity();
+        if needed <= full_capacity / 2 {
+            Some(self.data.len())
+        } else {
+            capacity_to_buckets(needed.max(full_capacity + 1), self.max_load)
+        }
+    }
+
+    /// Rebuilds the table with `buckets` slots, moving every entry into its
+    /// new slot.
+    fn resize(&mut self, buckets: usize) {
+        self.rebuild(empty_slots(buckets), empty_ctrl(buckets));
+    }
+
+    /// Moves every entry into `data`, whose control bytes are `ctrl`. Both
+    /// must be empty and sized for the same number of slots.
+    fn rebuild(&mut self, data: OwnedSlice<Option<KeyValue<K, V>>>, ctrl: OwnedSlice<u8>) {
+        let buckets = data.len();
+        debug_assert!(buckets == 0 || buckets.is_power_of_two());
+        let old_data = mem::replace(&mut self.data, data);
+        self.ctrl = ctrl;
+        self.bucket_mask = buckets.saturating_sub(1);
+        self.growth_left = bucket_capacity(buckets, self.max_load) - self.n_items;
+        let map = RecountOnUnwind(self);
+        for entry in old_data
+            .into_iter()
+            .filter_map(Into::<std::option::Option<KeyValue<K, V>>>::into)
+        {
+            // Keys are unique already, so each entry goes straight into the
+            // first free slot of its probe sequence.
+            let hash = map.0.hash(&entry.key);
+            let index = map.0.find_insert_slot(hash);
+            map.0.set_ctrl(index, group::h2(hash));
+            map.0.data[index] = Option::Some(entry);
+        }
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    fn with_capacity_and_max_load(capacity: usize, max_load: usize) -> Self {
+        let buckets =
+            capacity_to_buckets(capacity, max_load).expect("Hash table capacity overflow.");
         HashMap {
-            data,
+            data: empty_slots(buckets),
+            ctrl: empty_ctrl(buckets),
             n_items: 0,
-            capacity,
+            growth_left: bucket_capacity(buckets, max_load),
+            bucket_mask: buckets.saturating_sub(1),
+            max_load,
+            _hasher: PhantomData,
         }
     }
 
-    pub fn len(&self) -> usize {
-        self.n_items
+    /// Returns the number of entries the map can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        bucket_capacity(self.data.len(), self.max_load)
+    }
+
+    /// Returns the fraction of slots that may be filled before the map
+    /// grows.
+    pub fn max_load_factor(&self) -> f64 {
+        self.max_load as f64 / LOAD_FACTOR_SCALE as f64
+    }
+
+    pub fn len(&self) -> usize {
+        self.n_items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n_items == 0
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            slots: self.data.iter(),
+            remaining: self.n_items,
+        }
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order, with
+    /// mutable references to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            slots: self.data.iter_mut(),
+            remaining: self.n_items,
+        }
+    }
+
+    /// An iterator visiting all keys in arbitrary order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// An iterator visiting all values in arbitrary order.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    /// An iterator visiting all values mutably in arbitrary order.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// Creates a consuming iterator visiting all the keys in arbitrary order.
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys {
+            inner: self.into_iter(),
+        }
+    }
+
+    /// Creates a consuming iterator visiting all the values in arbitrary
+    /// order.
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues {
+            inner: self.into_iter(),
+        }
+    }
+
+    /// The raw slot array of the table, including the empty slots.
+    ///
+    /// This is the layout C++ sees, and is mostly useful to inspect the
+    /// placement of entries.
+    pub fn slots(&self) -> &[Option<KeyValue<K, V>>] {
+        &self.data
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for HashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// A diagnostic view of the slots of a [`HashMap`].
+///
+/// This struct is created by the [`debug_slots`](HashMap::debug_slots)
+/// method on [`HashMap`].
+pub struct DebugSlots<'a, K, V, S = DefaultHashBuilder> {
+    map: &'a HashMap<K, V, S>,
+}
+
+/// The state of one slot, as printed by [`DebugSlots`].
+enum SlotState<'a, K, V> {
+    Empty,
+    Deleted,
+    Full {
+        tag: u8,
+        probe_len: usize,
+        key: &'a K,
+        value: &'a V,
+    },
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for SlotState<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlotState::Empty => f.write_str("Empty"),
+            SlotState::Deleted => f.write_str("Deleted"),
+            SlotState::Full {
+                tag,
+                probe_len,
+                key,
+                value,
+            } => f
+                .debug_struct("Full")
+                .field("tag", &format_args!("{tag:#04x}"))
+                .field("probe_len", probe_len)
+                .field("key", key)
+                .field("value", value)
+                .finish(),
+        }
+    }
+}
+
+impl<K, V, S> fmt::Debug for DebugSlots<'_, K, V, S>
+where
+    K: fmt::Debug + Hash + Eq,
+    V: fmt::Debug,
+    S: BuildHasher + Default,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let map = self.map;
+        let mut max_probe_len = 0;
+        let slots: Vec<SlotState<'_, K, V>> = map
+            .data
+            .iter()
+            .zip(map.ctrl.iter())
+            .enumerate()
+            .map(|(index, (slot, &ctrl))| match slot {
+                Option::Some(entry) => {
+                    let probe_len = map.probe_len(map.hash(&entry.key), index);
+                    max_probe_len = max_probe_len.max(probe_len);
+                    SlotState::Full {
+                        tag: ctrl,
+                        probe_len,
+                        key: &entry.key,
+                        value: &entry.value,
+                    }
+                }
+                Option::None if ctrl == DELETED => SlotState::Deleted,
+                Option::None => SlotState::Empty,
+            })
+            .collect();
+        f.debug_struct("HashMap")
+            .field("len", &map.n_items)
+            .field("capacity", &map.capacity())
+            .field("growth_left", &map.growth_left)
+            .field("max_load_factor", &map.max_load_factor())
+            .field("group_width", &Group::WIDTH)
+            .field("max_probe_len", &max_probe_len)
+            .field("slots", &slots)
+            .finish()
+    }
+}
+
+impl<K, V, S> Default for HashMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_capacity_and_max_load(0, DEFAULT_MAX_LOAD)
+    }
+}
+
+impl<K: Hash + Eq, V: PartialEq, S: BuildHasher + Default> PartialEq for HashMap<K, V, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K: Hash + Eq, V: Eq, S: BuildHasher + Default> Eq for HashMap<K, V, S> {}
+
+impl<K: Hash + Eq, V: Hash, S: BuildHasher + Default> Hash for HashMap<K, V, S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_unordered::<_, _, S, _>(self.iter(), state);
+    }
+}
+
+/// Feeds the entries of a map into `state` independently of their order.
+///
+/// Every entry is hashed on its own with a hasher built from `S`, and the
+/// results are summed, so maps that compare equal hash equally.
+fn hash_unordered<'a, K, V, S, H>(
+    entries: impl ExactSizeIterator<Item = (&'a K, &'a V)>,
+    state: &mut H,
+) where
+    K: Hash + 'a,
+    V: Hash + 'a,
+    S: BuildHasher + Default,
+    H: Hasher,
+{
+    let hasher = S::default();
+    state.write_usize(entries.len());
+    let sum = entries.fold(0u64, |sum, entry| sum.wrapping_add(hasher.hash_one(entry)));
+    state.write_u64(sum);
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default, Q> Index<&Q> for HashMap<K, V, S>
+where
+    K: Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `HashMap`.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("Key not found in HashMap.")
+    }
+}
+
+/// A view into a single entry in a map, which may either be vacant or
+/// occupied.
+///
+/// This enum is constructed from the [`entry`](HashMap::entry) method on
+/// [`HashMap`].
+pub enum Entry<'a, K, V, S = DefaultHashBuilder> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+/// A view into an occupied entry in a [`HashMap`]. It is part of the
+/// [`Entry`] enum.
+pub struct OccupiedEntry<'a, K, V, S = DefaultHashBuilder> {
+    map: &'a mut HashMap<K, V, S>,
+    index: usize,
+}
+
+/// A view into a vacant entry in a [`HashMap`]. It is part of the [`Entry`]
+/// enum.
+pub struct VacantEntry<'a, K, V, S = DefaultHashBuilder> {
+    map: &'a mut HashMap<K, V, S>,
+    key: K,
+    hash: u64,
+    index: usize,
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher + Default> Entry<'a, K, V, S> {
+    /// Ensures a value is in the entry by inserting the default if empty, and
+    /// returns a mutable reference to the value in the entry.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default
+    /// function if empty, and returns a mutable reference to the value in the
+    /// entry.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default
+    /// function, which is given the key, if empty.
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if
+    /// empty, and returns a mutable reference to the value in the entry.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any
+    /// potential inserts into the map.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+
+    /// Returns a reference to this entry's key.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher + Default> OccupiedEntry<'a, K, V, S> {
+    /// Gets a reference to the key in the entry.
+    pub fn key(&self) -> &K {
+        &self.map.entry_at(self.index).key
+    }
+
+    /// Gets a reference to the value in the entry.
+    pub fn get(&self) -> &V {
+        &self.map.entry_at(self.index).value
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.entry_at_mut(self.index).value
+    }
+
+    /// Converts the entry into a mutable reference to the value in the entry
+    /// with a lifetime bound to the map itself.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.entry_at_mut(self.index).value
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Takes the value out of the entry, and returns it.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Takes the ownership of the key and value from the map.
+    pub fn remove_entry(self) -> (K, V) {
+        let entry = self.map.remove_slot(self.index);
+        (entry.key, entry.value)
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher + Default> VacantEntry<'a, K, V, S> {
+    /// Gets a reference to the key that would be used when inserting a value
+    /// through the `VacantEntry`.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Take ownership of the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Sets the value of the entry with the `VacantEntry`'s key, and returns a
+    /// mutable reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let entry = KeyValue {
+            key: self.key,
+            value,
+        };
+        self.map.insert_slot(self.index, self.hash, entry);
+        &mut self.map.entry_at_mut(self.index).value
+    }
+}
+
+/// An iterator over the entries of a [`HashMap`].
+///
+/// This struct is created by the [`iter`](HashMap::iter) method on
+/// [`HashMap`].
+pub struct Iter<'a, K, V> {
+    slots: slice::Iter<'a, Option<KeyValue<K, V>>>,
+    remaining: usize,
+}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Iter {
+            slots: self.slots.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> std::option::Option<Self::Item> {
+        let entry = self.slots.by_ref().find_map(|slot| slot.as_ref())?;
+        self.remaining -= 1;
+        Some((&entry.key, &entry.value))
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+/// A mutable iterator over the entries of a [`HashMap`].
+///
+/// This struct is created by the [`iter_mut`](HashMap::iter_mut) method on
+/// [`HashMap`].
+pub struct IterMut<'a, K, V> {
+    slots: slice::IterMut<'a, Option<KeyValue<K, V>>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> std::option::Option<Self::Item> {
+        let entry = self.slots.by_ref().find_map(|slot| slot.as_mut())?;
+        self.remaining -= 1;
+        Some((&entry.key, &mut entry.value))
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+/// An owning iterator over the entries of a [`HashMap`].
+///
+/// This struct is created by the `into_iter` method on [`HashMap`].
+pub struct IntoIter<K, V> {
+    slots: vec::IntoIter<Option<KeyValue<K, V>>>,
+    remaining: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> std::option::Option<Self::Item> {
+        let entry = self
+            .slots
+            .by_ref()
+            .find_map(Into::<std::option::Option<KeyValue<K, V>>>::into)?;
+        self.remaining -= 1;
+        Some((entry.key, entry.value))
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+/// An iterator over the keys of a [`HashMap`].
+///
+/// This struct is created by the [`keys`](HashMap::keys) method on
+/// [`HashMap`].
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<K, V> Clone for Keys<'_, K, V> {
+    fn clone(&self) -> Self {
+        Keys {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> std::option::Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+impl<K, V> FusedIterator for Keys<'_, K, V> {}
+
+/// An iterator over the values of a [`HashMap`].
+///
+/// This struct is created by the [`values`](HashMap::values) method on
+/// [`HashMap`].
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<K, V> Clone for Values<'_, K, V> {
+    fn clone(&self) -> Self {
+        Values {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> std::option::Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+impl<K, V> FusedIterator for Values<'_, K, V> {}
+
+/// A mutable iterator over the values of a [`HashMap`].
+///
+/// This struct is created by the [`values_mut`](HashMap::values_mut) method
+/// on [`HashMap`].
+pub struct ValuesMut<'a, K, V> {
+    inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> std::option::Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+impl<K, V> FusedIterator for ValuesMut<'_, K, V> {}
+
+/// An owning iterator over the keys of a [`HashMap`].
+///
+/// This struct is created by the [`into_keys`](HashMap::into_keys) method on
+/// [`HashMap`].
+pub struct IntoKeys<K, V> {
+    inner: IntoIter<K, V>,
+}
+
+impl<K, V> Iterator for IntoKeys<K, V> {
+    type Item = K;
+
+    fn next(&mut self) -> std::option::Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoKeys<K, V> {}
+impl<K, V> FusedIterator for IntoKeys<K, V> {}
+
+/// An owning iterator over the values of a [`HashMap`].
+///
+/// This struct is created by the [`into_values`](HashMap::into_values) method
+/// on [`HashMap`].
+pub struct IntoValues<K, V> {
+    inner: IntoIter<K, V>,
+}
+
+impl<K, V> Iterator for IntoValues<K, V> {
+    type Item = V;
+
+    fn next(&mut self) -> std::option::Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoValues<K, V> {}
+impl<K, V> FusedIterator for IntoValues<K, V> {}
+
+impl<K, V, S> IntoIterator for HashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            slots: self.data.into_vec().into_iter(),
+            remaining: self.n_items,
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a HashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut HashMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> FromIterator<(K, V)> for HashMap<K, V, S> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = HashMap::default();
+        map.extend(iter);
+        map
     }
+}
 
-    pub fn insert(&mut self, key: K, value: V) -> std::option::Option<V> {
-        if self.n_items > 3 * self.capacity / 4 {
-            self.double_capacity()
-        }
-        let index = hash(&key) % self.capacity as u64;
-        let temp = if let Some(temp) = self
-            .data
-            .iter_mut()
-            .skip(index as usize)
-            .find(|x| x.is_none())
-        {
-            temp
-        } else {
-            self.data
-                .iter_mut()
-                .find(|x| x.is_none())
-                .expect("Failed to find empty slot in hashmap.")
-        };
-        let result = if temp.is_some() {
-            mem::replace(temp, Option::None)
+impl<K: Hash + Eq, V, S: BuildHasher + Default> Extend<(K, V)> for HashMap<K, V, S> {
+    /// Inserts all key-value pairs of the iterator, growing the table once
+    /// up front based on the size hint.
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        let iter = iter.into_iter();
+        // Keys of a non-empty map may already be present, so only half of
+        // the hint is reserved as std does.
+        let additional = if self.is_empty() {
+            iter.size_hint().0
         } else {
-            Option::None
+            iter.size_hint().0.div_ceil(2)
         };
-        *temp = Option::Some(KeyValue {
-            key: key.clone(),
-            value,
-        });
-        self.n_items += 1;
-        result.map(|x| x.value).into()
+        self.reserve(additional);
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
     }
+}
 
-    pub fn get<Q>(&self, key: &Q) -> std::option::Option<&V>
-    where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
-    {
-        let index = hash(&key) % self.capacity as u64;
-        self.data
-            .iter()
-            .cycle()
-            .skip(index as usize)
-            .find(|x| {
-                if let Option::Some(entry) = x {
-                    *entry.key.borrow() == *key
-                } else {
-                    false
-                }
-            })
-            .and_then(|x| x.as_ref().map(|x| &x.value))
+impl<'a, K: Hash + Eq + Copy, V: Copy, S: BuildHasher + Default> Extend<(&'a K, &'a V)>
+    for HashMap<K, V, S>
+{
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
     }
+}
 
-    pub fn iter(&self) -> Iter<Option<KeyValue<K, V>>> {
-        self.data.iter()
+impl<K: Hash + Eq, V, const N: usize> From<[(K, V); N]> for HashMap<K, V> {
+    fn from(entries: [(K, V); N]) -> Self {
+        HashMap::from_iter(entries)
     }
+}
 
-    fn double_capacity(&mut self) {
-        self.capacity *= 2;
-        let new_data = OwnedSlice::from(vec![Option::None; self.capacity]);
-        let old_data = mem::replace(&mut self.data, new_data);
-        self.n_items = 0;
-        for entry in old_data
-            .into_iter()
-            .filter_map(|x| Into::<std::option::Option<KeyValue<K, V>>>::into(x))
-        {
-            self.insert(entry.key, entry.value)
-                .expect("Capacity should be enough.");
-        }
+impl<K: Hash + Eq, V, S: BuildHasher + Default, T> From<std::collections::HashMap<K, V, T>>
+    for HashMap<K, V, S>
+{
+    fn from(map: std::collections::HashMap<K, V, T>) -> Self {
+        HashMap::from_iter(map)
+    }
+}
+
+impl<K: Hash + Eq, V, S> From<HashMap<K, V, S>> for std::collections::HashMap<K, V> {
+    fn from(map: HashMap<K, V, S>) -> Self {
+        std::collections::HashMap::from_iter(map)
     }
 }
 
-impl<K: Clone + Eq + Hash, V: Clone> Serialize for HashMap<K, V>
+impl<K, V, H> Serialize for HashMap<K, V, H>
 where
     K: Serialize,
     V: Serialize,
@@ -134,22 +1351,19 @@ where
         S: Serializer,
     {
         let mut map = serializer.serialize_map(Some(self.len()))?;
-        for entry in self
-            .data
-            .iter()
-            .filter_map(|x| Into::<std::option::Option<&KeyValue<K, V>>>::into(x.as_ref()))
-        {
-            map.serialize_entry(&entry.key, &entry.value)?;
+        for (key, value) in self {
+            map.serialize_entry(key, value)?;
         }
         map.end()
     }
 }
 
-struct MapVisitor<K, V> {
-    marker: PhantomData<fn() -> HashMap<K, V>>,
+struct MapVisitor<K, V, S> {
+    #[allow(clippy::type_complexity)]
+    marker: PhantomData<fn() -> HashMap<K, V, S>>,
 }
 
-impl<K, V> MapVisitor<K, V> {
+impl<K, V, S> MapVisitor<K, V, S> {
     fn new() -> Self {
         MapVisitor {
             marker: PhantomData,
@@ -157,12 +1371,13 @@ impl<K, V> MapVisitor<K, V> {
     }
 }
 
-impl<'de, K, V> Visitor<'de> for MapVisitor<K, V>
+impl<'de, K, V, S> Visitor<'de> for MapVisitor<K, V, S>
 where
-    K: Deserialize<'de> + Clone + Hash + Eq,
-    V: Deserialize<'de> + Clone,
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
 {
-    type Value = HashMap<K, V>;
+    type Value = HashMap<K, V, S>;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         formatter.write_str("a very special map")
@@ -172,7 +1387,10 @@ where
     where
         M: MapAccess<'de>,
     {
-        let mut map = HashMap::with_capacity(access.size_hint().unwrap_or(1));
+        let mut map = HashMap::with_capacity_and_max_load(
+            cautious_capacity(access.size_hint()),
+            DEFAULT_MAX_LOAD,
+        );
 
         while let Some((key, value)) = access.next_entry()? {
             map.insert(key, value);
@@ -182,10 +1400,11 @@ where
     }
 }
 
-impl<'de, K, V> Deserialize<'de> for HashMap<K, V>
+impl<'de, K, V, S> Deserialize<'de> for HashMap<K, V, S>
 where
-    K: Deserialize<'de> + Clone + Hash + Eq,
-    V: Deserialize<'de> + Clone,
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
 {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -197,9 +1416,25 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::hash::{Hash, Hasher};
+    use std::mem;
+
     use crate::owned_str::OwnedStr;
 
-    use super::HashMap;
+    use super::{Entry, HashMap, TryReserveError};
+
+    /// A key whose hash only depends on `group`, to force collisions.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct Collide {
+        group: u32,
+        id: u32,
+    }
+
+    impl Hash for Collide {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.group.hash(state)
+        }
+    }
 
     #[test]
     fn hashmap_insert_get() {
@@ -213,4 +1448,699 @@ mod tests {
         let val2 = map.get(&key2).expect("Failed to get value.");
         assert_eq!(*val2, 16);
     }
+
+    #[test]
+    fn hashmap_get_absent() {
+        let mut map: HashMap<OwnedStr, i32> = HashMap::new();
+        let missing: OwnedStr = "missing".to_string().into();
+        assert_eq!(map.get(&missing), None);
+        for i in 0..10 {
+            map.insert(format!("key{}", i).into(), i);
+        }
+        assert_eq!(map.get(&missing), None);
+    }
+
+    #[test]
+    fn hashmap_get_full_table() {
+        let mut map = HashMap::with_capacity(6);
+        for i in 0..6 {
+            map.insert(i, i * 10);
+        }
+        assert_eq!(map.len(), 6);
+        assert_eq!(map.slots().len(), 8);
+        for i in 0..6 {
+            assert_eq!(map.get(&i), Some(&(i * 10)));
+        }
+        assert_eq!(map.get(&6), None);
+        assert_eq!(map.get(&-1), None);
+    }
+
+    #[test]
+    fn hashmap_insert_existing_key() {
+        let mut map = HashMap::new();
+        let key: OwnedStr = "test".to_string().into();
+        assert_eq!(map.insert(key.clone(), 1), None);
+        assert_eq!(map.insert(key.clone(), 2), Some(1));
+        assert_eq!(map.insert(key.clone(), 3), Some(2));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&key), Some(&3));
+        assert_eq!(map.slots().iter().filter(|x| x.is_some()).count(), 1);
+    }
+
+    #[test]
+    fn hashmap_insert_existing_key_after_resize() {
+        let mut map = HashMap::with_capacity(4);
+        for i in 0..50 {
+            assert_eq!(map.insert(i, i), None);
+        }
+        for i in 0..50 {
+            assert_eq!(map.insert(i, i + 1), Some(i));
+        }
+        assert_eq!(map.len(), 50);
+    }
+
+    #[test]
+    fn hashmap_deserialize_duplicate_keys() {
+        let record = r#"{"a": 1, "b": 2, "a": 3}"#;
+        let map: HashMap<OwnedStr, i32> = serde_json::from_str(record).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&OwnedStr::from("a".to_string())), Some(&3));
+        assert_eq!(map.get(&OwnedStr::from("b".to_string())), Some(&2));
+        let json = serde_json::to_string(&map).unwrap();
+        let map_two: HashMap<OwnedStr, i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(map_two.len(), 2);
+        assert_eq!(map_two.get(&OwnedStr::from("a".to_string())), Some(&3));
+        assert_eq!(map_two, map);
+    }
+
+    #[test]
+    fn hashmap_get_after_resize() {
+        let mut map = HashMap::with_capacity(4);
+        for i in 0..100 {
+            map.insert(i, i * 2);
+        }
+        assert_eq!(map.len(), 100);
+        for i in 0..100 {
+            assert_eq!(map.get(&i), Some(&(i * 2)));
+        }
+        for i in 100..200 {
+            assert_eq!(map.get(&i), None);
+        }
+    }
+
+    #[test]
+    fn hashmap_remove() {
+        let mut map = HashMap::new();
+        let key1: OwnedStr = "test1".to_string().into();
+        let key2: OwnedStr = "test2".to_string().into();
+        map.insert(key1.clone(), 8);
+        map.insert(key2.clone(), 16);
+        assert_eq!(map.remove(&key1), Some(8));
+        assert_eq!(map.remove(&key1), None);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&key1), None);
+        assert_eq!(map.remove_entry(&key2), Some((key2.clone(), 16)));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn hashmap_remove_keeps_probe_chain() {
+        let mut map = HashMap::with_capacity(16);
+        for id in 0..6 {
+            map.insert(Collide { group: 0, id }, id);
+        }
+        for id in 0..3 {
+            map.insert(Collide { group: 1, id }, 10 + id);
+        }
+        assert_eq!(map.remove(&Collide { group: 0, id: 0 }), Some(0));
+        assert_eq!(map.remove(&Collide { group: 0, id: 3 }), Some(3));
+        assert_eq!(map.len(), 7);
+        for id in [1, 2, 4, 5] {
+            assert_eq!(map.get(&Collide { group: 0, id }), Some(&id));
+        }
+        for id in 0..3 {
+            assert_eq!(map.get(&Collide { group: 1, id }), Some(&(10 + id)));
+        }
+        assert_eq!(map.get(&Collide { group: 0, id: 3 }), None);
+    }
+
+    #[test]
+    fn hashmap_remove_full_table() {
+        let mut map = HashMap::with_capacity(6);
+        for id in 0..6 {
+            map.insert(Collide { group: 0, id }, id);
+        }
+        assert_eq!(map.slots().len(), 8);
+        for id in 0..6 {
+            assert_eq!(map.remove(&Collide { group: 0, id }), Some(id));
+            for rest in id + 1..6 {
+                assert_eq!(map.get(&Collide { group: 0, id: rest }), Some(&rest));
+            }
+        }
+        assert!(map.is_empty());
+        assert!(map.slots().iter().all(|x| x.is_none()));
+    }
+
+    #[test]
+    fn hashmap_retain() {
+        let mut map = HashMap::with_capacity(8);
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        map.retain(|key, value| {
+            *value *= 2;
+            key % 3 == 0
+        });
+        assert_eq!(map.len(), 34);
+        for i in 0..100 {
+            if i % 3 == 0 {
+                assert_eq!(map.get(&i), Some(&(i * 2)));
+            } else {
+                assert_eq!(map.get(&i), None);
+            }
+        }
+    }
+
+    #[test]
+    fn hashmap_entry_counting() {
+        let mut map: HashMap<OwnedStr, usize> = HashMap::with_capacity(4);
+        for word in "a b c a b a d e f g a".split(' ') {
+            *map.entry(word.to_string().into()).or_insert(0) += 1;
+        }
+        assert_eq!(map.len(), 7);
+        assert_eq!(map.get(&OwnedStr::from("a".to_string())), Some(&4));
+        assert_eq!(map.get(&OwnedStr::from("b".to_string())), Some(&2));
+        assert_eq!(map.get(&OwnedStr::from("g".to_string())), Some(&1));
+    }
+
+    #[test]
+    fn hashmap_entry_and_modify() {
+        let mut map = HashMap::new();
+        map.entry(1).and_modify(|v| *v += 1).or_insert(10);
+        assert_eq!(map.get(&1), Some(&10));
+        map.entry(1).and_modify(|v| *v += 1).or_insert(10);
+        assert_eq!(map.get(&1), Some(&11));
+        assert_eq!(*map.entry(2).or_default(), 0);
+        assert_eq!(*map.entry(3).or_insert_with(|| 30), 30);
+        assert_eq!(*map.entry(4).or_insert_with_key(|k| k * 10), 40);
+        assert_eq!(map.len(), 4);
+    }
+
+    #[test]
+    fn hashmap_entry_occupied_remove() {
+        let mut map = HashMap::with_capacity(8);
+        for id in 0..4 {
+            map.insert(Collide { group: 0, id }, id);
+        }
+        match map.entry(Collide { group: 0, id: 1 }) {
+            Entry::Occupied(entry) => {
+                assert_eq!(entry.key(), &Collide { group: 0, id: 1 });
+                assert_eq!(entry.remove(), 1);
+            }
+            Entry::Vacant(_) => panic!("Entry should be occupied."),
+        }
+        match map.entry(Collide { group: 0, id: 1 }) {
+            Entry::Occupied(_) => panic!("Entry should be vacant."),
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), Collide { group: 0, id: 1 }),
+        }
+        assert_eq!(map.len(), 3);
+        for id in [0, 2, 3] {
+            assert_eq!(map.get(&Collide { group: 0, id }), Some(&id));
+        }
+    }
+
+    #[test]
+    fn hashmap_get_mut() {
+        let mut map: HashMap<OwnedStr, i32> = HashMap::new();
+        let key: OwnedStr = "counter".to_string().into();
+        map.insert(key.clone(), 1);
+        *map.get_mut(&key).expect("Failed to get value.") += 1;
+        assert_eq!(map[&key], 2);
+        assert!(map.contains_key(&key));
+        assert_eq!(map.get_key_value(&key), Some((&key, &2)));
+        let missing: OwnedStr = "missing".to_string().into();
+        assert_eq!(map.get_mut(&missing), None);
+        assert!(!map.contains_key(&missing));
+        assert_eq!(map.get_key_value(&missing), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn hashmap_index_missing() {
+        let map: HashMap<i32, i32> = HashMap::new();
+        let _ = map[&1];
+    }
+
+    #[test]
+    fn hashmap_get_many_mut() {
+        let mut map = HashMap::new();
+        map.insert(1, 10);
+        map.insert(2, 20);
+        map.insert(3, 30);
+        let [a, b] = map.get_many_mut([&1, &3]).expect("Failed to get values.");
+        mem::swap(a, b);
+        assert_eq!(map[&1], 30);
+        assert_eq!(map[&3], 10);
+        assert!(map.get_many_mut([&1, &1]).is_none());
+        assert!(map.get_many_mut([&1, &4]).is_none());
+    }
+
+    #[test]
+    fn hashmap_debug() {
+        let mut map = HashMap::with_capacity(40);
+        map.insert(1, "one");
+        assert_eq!(format!("{:?}", map), r#"{1: "one"}"#);
+        assert_eq!(format!("{:?}", HashMap::<i32, i32>::new()), "{}");
+
+        let dump = format!("{:?}", map.debug_slots());
+        assert!(dump.starts_with("HashMap { len: 1, capacity: 48, growth_left: 47,"));
+        assert!(dump.contains("max_probe_len: 1"));
+        assert_eq!(dump.matches("Empty").count(), 63);
+        assert!(dump.contains(r#"probe_len: 1, key: 1, value: "one" }"#));
+
+        let mut map = HashMap::new();
+        for id in 0..40 {
+            map.insert(Collide { group: 0, id }, id);
+        }
+        map.remove(&Collide { group: 0, id: 0 });
+        let dump = format!("{:#?}", map.debug_slots());
+        assert!(dump.contains("Deleted"));
+        assert!(!dump.contains("max_probe_len: 1,"));
+    }
+
+    #[test]
+    fn hashmap_aligned_keys() {
+        // Keys that only differ in their high bits must not share their home
+        // slot.
+        let mut map = HashMap::new();
+        for i in 0..4096u64 {
+            map.insert(i << 20, i);
+        }
+        let dump = format!("{:?}", map.debug_slots());
+        let max_probe_len: usize = dump
+            .split("max_probe_len: ")
+            .nth(1)
+            .and_then(|rest| rest.split(',').next())
+            .and_then(|len| len.parse().ok())
+            .unwrap();
+        assert!(max_probe_len <= 4, "max_probe_len: {max_probe_len}");
+        assert!((0..4096u64).all(|i| map[&(i << 20)] == i));
+    }
+
+    #[test]
+    fn hashmap_iter() {
+        let mut map = HashMap::with_capacity(8);
+        for i in 0..20 {
+            map.insert(i, i * 10);
+        }
+        let iter = map.iter();
+        assert_eq!(iter.len(), 20);
+        let mut entries: Vec<(i32, i32)> = iter.map(|(k, v)| (*k, *v)).collect();
+        entries.sort();
+        assert_eq!(entries, (0..20).map(|i| (i, i * 10)).collect::<Vec<_>>());
+
+        let mut keys: Vec<i32> = map.keys().copied().collect();
+        keys.sort();
+        assert_eq!(keys, (0..20).collect::<Vec<_>>());
+        assert_eq!(map.values().sum::<i32>(), 1900);
+
+        for (key, value) in &mut map {
+            *value += key;
+        }
+        for value in map.values_mut() {
+            *value += 1;
+        }
+        assert_eq!(map[&3], 34);
+
+        let mut iter = map.iter();
+        iter.next();
+        assert_eq!(iter.len(), 19);
+        assert_eq!((&map).into_iter().count(), 20);
+    }
+
+    #[test]
+    fn hashmap_into_iter() {
+        let mut map = HashMap::new();
+        for i in 0..5 {
+            map.insert(i, i.to_string());
+        }
+        let mut entries: Vec<(i32, String)> = map.clone().into_iter().collect();
+        entries.sort();
+        assert_eq!(
+            entries,
+            (0..5).map(|i| (i, i.to_string())).collect::<Vec<_>>()
+        );
+        let mut keys: Vec<i32> = map.clone().into_keys().collect();
+        keys.sort();
+        assert_eq!(keys, vec![0, 1, 2, 3, 4]);
+        let values = map.into_values();
+        assert_eq!(values.len(), 5);
+    }
+
+    #[test]
+    fn hashmap_from_iter() {
+        let map: HashMap<i32, i32> = (0..100).map(|i| (i, i * i)).collect();
+        assert_eq!(map.len(), 100);
+        assert!(map.slots().len() >= 128);
+        assert_eq!(map[&9], 81);
+
+        let mut map = HashMap::from([(1, 1), (2, 2), (1, 3)]);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&1], 3);
+        map.extend([(3, 3), (4, 4)]);
+        map.extend([(&5, &5), (&1, &10)]);
+        assert_eq!(map.len(), 5);
+        assert_eq!(map[&1], 10);
+
+        let empty: HashMap<i32, i32> = std::iter::empty().collect();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn hashmap_std_conversion() {
+        let mut std_map = std::collections::HashMap::new();
+        std_map.insert(OwnedStr::from("a".to_string()), 1);
+        std_map.insert(OwnedStr::from("b".to_string()), 2);
+        let map: HashMap<OwnedStr, i32> = std_map.clone().into();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&OwnedStr::from("b".to_string())], 2);
+        let round_trip: std::collections::HashMap<OwnedStr, i32> = map.into();
+        assert_eq!(round_trip, std_map);
+    }
+
+    #[test]
+    fn hashmap_non_clone_values() {
+        struct Handle(i32);
+
+        let mut map: HashMap<OwnedStr, Box<dyn Fn() -> i32>> = HashMap::with_capacity(2);
+        for i in 0..10 {
+            map.insert(format!("f{}", i).into(), Box::new(move || i * 2));
+        }
+        assert_eq!(map[&OwnedStr::from("f7".to_string())](), 14);
+
+        let mut handles = HashMap::with_capacity(2);
+        for i in 0..10 {
+            handles.insert(i, Handle(i));
+        }
+        assert_eq!(handles.remove(&3).map(|h| h.0), Some(3));
+        assert_eq!(handles.len(), 9);
+        assert_eq!(handles.get(&4).map(|h| h.0), Some(4));
+    }
+
+    #[test]
+    fn hashmap_deserialize_caps_size_hint() {
+        use serde::de::value::{Error, MapAccessDeserializer};
+        use serde::de::{DeserializeSeed, MapAccess};
+        use serde::Deserialize;
+
+        /// An empty map that claims to hold `usize::MAX` entries.
+        struct HugeHint;
+
+        impl<'de> MapAccess<'de> for HugeHint {
+            type Error = Error;
+
+            fn next_key_seed<K: DeserializeSeed<'de>>(
+                &mut self,
+                _seed: K,
+            ) -> Result<Option<K::Value>, Error> {
+                Ok(None)
+            }
+
+            fn next_value_seed<V: DeserializeSeed<'de>>(
+                &mut self,
+                _seed: V,
+            ) -> Result<V::Value, Error> {
+                unreachable!("There are no keys.")
+            }
+
+            fn size_hint(&self) -> Option<usize> {
+                Some(usize::MAX)
+            }
+        }
+
+        let map = HashMap::<u64, u64>::deserialize(MapAccessDeserializer::new(HugeHint)).unwrap();
+        assert!(map.is_empty() && map.capacity() >= 4096);
+    }
+
+    #[test]
+    fn hashmap_panic_in_rebuild_keeps_count() {
+        use std::hash::Hasher;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        use std::sync::atomic::{AtomicI32, Ordering};
+
+        /// The key that panics when it is hashed.
+        static POISONED: AtomicI32 = AtomicI32::new(-1);
+
+        #[derive(Debug, PartialEq, Eq)]
+        struct Key(i32);
+
+        impl std::hash::Hash for Key {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                assert_ne!(self.0, POISONED.load(Ordering::Relaxed));
+                self.0.hash(state)
+            }
+        }
+
+        let mut map = HashMap::new();
+        for i in 0..20 {
+            map.insert(Key(i), i);
+        }
+        POISONED.store(7, Ordering::Relaxed);
+        let result = catch_unwind(AssertUnwindSafe(|| map.reserve(1000)));
+        assert!(result.is_err());
+        POISONED.store(-1, Ordering::Relaxed);
+
+        assert!(map.len() < 20);
+        assert_eq!(map.iter().count(), map.len());
+        let left: Vec<i32> = map.keys().map(|key| key.0).collect();
+        for i in left {
+            assert_eq!(map[&Key(i)], i);
+        }
+        for i in 0..20 {
+            map.insert(Key(i), i);
+        }
+        assert_eq!(map.len(), 20);
+        assert_eq!(map.iter().count(), 20);
+    }
+
+    #[test]
+    fn hashmap_custom_hasher() {
+        #[derive(Default)]
+        struct StdHasher;
+
+        impl std::hash::BuildHasher for StdHasher {
+            type Hasher = std::collections::hash_map::DefaultHasher;
+
+            fn build_hasher(&self) -> Self::Hasher {
+                Self::Hasher::new()
+            }
+        }
+
+        let mut map: HashMap<i32, i32, StdHasher> = HashMap::default();
+        map.reserve(4);
+        for i in 0..20 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.len(), 20);
+        assert_eq!(map[&7], 7);
+        let json = serde_json::to_string(&map).unwrap();
+        let map_two: HashMap<i32, i32, StdHasher> = serde_json::from_str(&json).unwrap();
+        assert_eq!(map_two[&19], 19);
+    }
+
+    #[test]
+    fn hashmap_hash_owned_str() {
+        use std::hash::BuildHasher;
+
+        let key: OwnedStr = "hello world".to_string().into();
+        assert_eq!(
+            super::crust_hash_owned_str(&key),
+            super::DefaultHashBuilder::default().hash_one(&key)
+        );
+    }
+
+    #[test]
+    fn hashmap_capacity() {
+        let mut map: HashMap<i32, i32> = HashMap::with_capacity(0);
+        assert_eq!(map.capacity(), 0);
+        assert!(map.slots().is_empty());
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.remove(&1), None);
+        assert!(HashMap::<i32, i32>::new().slots().is_empty());
+
+        map.insert(1, 1);
+        assert_eq!(map.slots().len(), 4);
+        assert_eq!(map.capacity(), 3);
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        assert!(map.slots().len().is_power_of_two());
+        assert!(map.capacity() >= 100);
+
+        for requested in [1, 3, 5, 6, 7, 12, 13, 100, 1000] {
+            let map: HashMap<i32, i32> = HashMap::with_capacity(requested);
+            assert!(map.slots().len().is_power_of_two());
+            assert!(map.capacity() >= requested);
+            assert!(map.capacity() < 2 * requested.max(3));
+        }
+    }
+
+    #[test]
+    fn hashmap_fill_to_capacity() {
+        let mut map = HashMap::with_capacity(12);
+        let buckets = map.slots().len();
+        for i in 0..map.capacity() as i32 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.slots().len(), buckets);
+        assert_eq!(map.get(&-1), None);
+    }
+
+    #[test]
+    fn hashmap_reserve_and_shrink() {
+        let mut map: HashMap<i32, i32> = HashMap::new();
+        map.reserve(100);
+        assert!(map.capacity() >= 100);
+        let buckets = map.slots().len();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.slots().len(), buckets);
+
+        map.retain(|key, _| *key < 10);
+        map.shrink_to(50);
+        assert!(map.capacity() >= 50);
+        assert!(map.slots().len() < buckets);
+        map.shrink_to_fit();
+        assert_eq!(map.slots().len(), 16);
+        for i in 0..10 {
+            assert_eq!(map[&i], i);
+        }
+        assert_eq!(map.get(&10), None);
+
+        map.retain(|_, _| false);
+        map.shrink_to_fit();
+        assert!(map.slots().is_empty());
+        map.insert(1, 1);
+        assert_eq!(map[&1], 1);
+    }
+
+    #[test]
+    fn hashmap_try_reserve() {
+        let mut map: HashMap<i32, i32> = HashMap::new();
+        assert_eq!(map.try_reserve(10), Ok(()));
+        assert!(map.capacity() >= 10);
+        map.insert(1, 1);
+        assert_eq!(
+            map.try_reserve(usize::MAX),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert!(matches!(
+            map.try_reserve(usize::MAX / 2048),
+            Err(TryReserveError::AllocError(_))
+        ));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[&1], 1);
+    }
+
+    #[test]
+    fn hashmap_max_load_factor() {
+        let mut map: HashMap<i32, i32> = HashMap::with_capacity_and_max_load_factor(0, 0.5);
+        assert_eq!(map.max_load_factor(), 0.5);
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        assert!(map.len() * 2 <= map.slots().len());
+        assert_eq!(map.slots().len(), 256);
+
+        let mut map: HashMap<i32, i32> = HashMap::with_capacity_and_max_load_factor(0, 0.99);
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.slots().len(), 1024);
+        assert!(map.capacity() < 1024);
+        for i in 0..1000 {
+            assert_eq!(map[&i], i);
+        }
+        assert_eq!(map.get(&1000), None);
+        assert_eq!(HashMap::<i32, i32>::new().max_load_factor(), 0.75);
+    }
+
+    #[test]
+    #[should_panic]
+    fn hashmap_invalid_max_load_factor() {
+        HashMap::<i32, i32>::with_capacity_and_max_load_factor(0, 1.0);
+    }
+
+    #[test]
+    fn hashmap_eq_and_hash() {
+        fn hash_of<T: Hash>(value: &T) -> u64 {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let forward: HashMap<i32, i32> = (0..100).map(|i| (i, i * 2)).collect();
+        let mut backward = HashMap::with_capacity_and_max_load_factor(1000, 0.5);
+        for i in (0..100).rev() {
+            backward.insert(i, i * 2);
+        }
+        backward.insert(100, 0);
+        assert_ne!(forward, backward);
+        backward.remove(&100);
+        assert_eq!(forward, backward);
+        assert_eq!(hash_of(&forward), hash_of(&backward));
+
+        backward.insert(5, 0);
+        assert_ne!(forward, backward);
+        assert_ne!(hash_of(&forward), hash_of(&backward));
+
+        let json = serde_json::to_string(&forward).unwrap();
+        let round_trip: HashMap<i32, i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_trip, forward);
+
+        let mut maps: HashMap<HashMap<i32, i32>, usize> = HashMap::new();
+        *maps.entry(forward).or_default() += 1;
+        *maps.entry(round_trip).or_default() += 1;
+        assert_eq!(maps.len(), 1);
+        assert_eq!(maps.values().next(), Some(&2));
+    }
+
+    #[test]
+    fn hashmap_many_collisions() {
+        let mut map = HashMap::new();
+        for id in 0..100 {
+            map.insert(Collide { group: id % 2, id }, id);
+        }
+        for id in (0..100).step_by(3) {
+            assert_eq!(map.remove(&Collide { group: id % 2, id }), Some(id));
+        }
+        for id in 0..100 {
+            let expected = if id % 3 == 0 { None } else { Some(&id) };
+            assert_eq!(map.get(&Collide { group: id % 2, id }), expected);
+        }
+    }
+
+    #[test]
+    fn hashmap_insert_remove_churn() {
+        let mut map = HashMap::with_capacity(16);
+        let buckets = map.slots().len();
+        for round in 0..1000 {
+            for i in 0..8 {
+                map.insert(round * 8 + i, i);
+            }
+            for i in 0..8 {
+                assert_eq!(map.remove(&(round * 8 + i)), Some(i));
+            }
+            assert!(map.is_empty());
+        }
+        assert_eq!(map.slots().len(), buckets);
+        for i in 0..12 {
+            map.insert(i, i);
+        }
+        for i in 0..12 {
+            assert_eq!(map[&i], i);
+        }
+        assert_eq!(map.get(&12), None);
+    }
+
+    #[test]
+    fn hashmap_matches_std() {
+        let mut map = HashMap::new();
+        let mut expected = std::collections::HashMap::new();
+        let mut state: u64 = 1;
+        for _ in 0..20000 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            let key = (state >> 33) % 512;
+            match (state >> 20) % 3 {
+                0 => assert_eq!(map.remove(&key), expected.remove(&key)),
+                _ => assert_eq!(map.insert(key, state), expected.insert(key, state)),
+            }
+            assert_eq!(map.len(), expected.len());
+        }
+        for key in 0..512 {
+            assert_eq!(map.get(&key), expected.get(&key));
+        }
+        assert_eq!(map.iter().count(), expected.len());
+    }
 }
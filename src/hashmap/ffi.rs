@@ -0,0 +1,225 @@
+//! C functions for concrete `HashMap` instantiations.
+//!
+//! cbindgen cannot expand macros, so [`export_hashmap!`](crate::export_hashmap)
+//! has a twin in `crust_std.h`: `CRUST_EXPORT_HASHMAP(K, V, PREFIX)` declares
+//! the same functions for C++ and connects them to the methods of the
+//! `HashMap` template. Every instantiation exported from Rust needs a matching
+//! invocation on the C++ side, with the same prefix.
+//!
+//! `tests/header.rs` links C++ code that uses the header against the
+//! instantiations exported below, so that both sides stay in sync.
+
+/// Exports C functions for `HashMap<$key, $value>` under the symbol prefix
+/// `crust_hashmap_<prefix>_`.
+///
+/// The generated functions are:
+///
+/// - `init(*mut HashMap)` writes an empty map into uninitialized storage.
+/// - `len(&HashMap) -> usize`
+/// - `get(&HashMap, &K) -> *const V` returns null if the key is missing.
+/// - `contains(&HashMap, &K) -> bool`
+/// - `insert(&mut HashMap, &mut K, &mut V) -> bool` moves the key and value
+///   into the map, leaving their defaults behind, and returns whether the
+///   key was new. An existing value is replaced.
+/// - `remove(&mut HashMap, &K, *mut V) -> bool` writes the removed value to
+///   the third argument unless it is null, and returns whether the key was
+///   present. The storage is treated as uninitialized: a value that was
+///   there before is overwritten without being dropped.
+/// - `next(&HashMap, &mut usize) -> *const KeyValue` returns the next entry
+///   at or after the cursor, which starts at zero, and advances the cursor.
+///   It returns null after the last entry.
+/// - `free(&mut HashMap)` drops all entries and releases the table.
+///
+/// Both `$key` and `$value` must implement `Default`.
+///
+/// ```
+/// use crust_std::{export_hashmap, owned_str::OwnedStr};
+///
+/// export_hashmap!(OwnedStr, u32, prefix = "doc_str_u32");
+/// ```
+#[macro_export]
+macro_rules! export_hashmap {
+    ($key:ty, $value:ty, prefix = $prefix:literal) => {
+        const _: () = {
+            use std::mem;
+
+            use $crate::hashmap::{HashMap, KeyValue};
+
+            /// # Safety
+            ///
+            /// `map` must be valid for writes and properly aligned.
+            #[export_name = concat!("crust_hashmap_", $prefix, "_init")]
+            pub unsafe extern "C" fn init(map: *mut HashMap<$key, $value>) {
+                map.write(HashMap::new());
+            }
+
+            #[export_name = concat!("crust_hashmap_", $prefix, "_len")]
+            pub extern "C" fn len(map: &HashMap<$key, $value>) -> usize {
+                map.len()
+            }
+
+            #[export_name = concat!("crust_hashmap_", $prefix, "_get")]
+            pub extern "C" fn get<'a>(
+                map: &'a HashMap<$key, $value>,
+                key: &$key,
+            ) -> std::option::Option<&'a $value> {
+                map.get(key)
+            }
+
+            #[export_name = concat!("crust_hashmap_", $prefix, "_contains")]
+            pub extern "C" fn contains(map: &HashMap<$key, $value>, key: &$key) -> bool {
+                map.contains_key(key)
+            }
+
+            #[export_name = concat!("crust_hashmap_", $prefix, "_insert")]
+            pub extern "C" fn insert(
+                map: &mut HashMap<$key, $value>,
+                key: &mut $key,
+                value: &mut $value,
+            ) -> bool {
+                map.insert(mem::take(key), mem::take(value)).is_none()
+            }
+
+            /// # Safety
+            ///
+            /// `out_value` must be null or valid for writes and properly
+            /// aligned.
+            #[export_name = concat!("crust_hashmap_", $prefix, "_remove")]
+            pub unsafe extern "C" fn remove(
+                map: &mut HashMap<$key, $value>,
+                key: &$key,
+                out_value: *mut $value,
+            ) -> bool {
+                let Some(value) = map.remove(key) else {
+                    return false;
+                };
+                if !out_value.is_null() {
+                    out_value.write(value);
+                }
+                true
+            }
+
+            #[export_name = concat!("crust_hashmap_", $prefix, "_next")]
+            pub extern "C" fn next<'a>(
+                map: &'a HashMap<$key, $value>,
+                cursor: &mut usize,
+            ) -> std::option::Option<&'a KeyValue<$key, $value>> {
+                let slots = map.slots().get(*cursor..).unwrap_or_default();
+                let (offset, entry) = slots
+                    .iter()
+                    .enumerate()
+                    .find_map(|(offset, slot)| Some((offset, slot.as_ref()?)))?;
+                *cursor += offset + 1;
+                Some(entry)
+            }
+
+            #[export_name = concat!("crust_hashmap_", $prefix, "_free")]
+            pub extern "C" fn free(map: &mut HashMap<$key, $value>) {
+                *map = HashMap::new();
+            }
+        };
+    };
+}
+
+export_hashmap!(crate::owned_str::OwnedStr, i64, prefix = "str_i64");
+export_hashmap!(
+    crate::owned_str::OwnedStr,
+    crate::owned_str::OwnedStr,
+    prefix = "str_str"
+);
+
+#[cfg(test)]
+mod tests {
+    use std::mem::MaybeUninit;
+    use std::ptr;
+
+    use crate::hashmap::{HashMap, KeyValue};
+    use crate::owned_str::OwnedStr;
+
+    type Map = HashMap<OwnedStr, i64>;
+
+    extern "C" {
+        fn crust_hashmap_str_i64_init(map: *mut Map);
+        fn crust_hashmap_str_i64_len(map: &Map) -> usize;
+        fn crust_hashmap_str_i64_get<'a>(map: &'a Map, key: &OwnedStr) -> Option<&'a i64>;
+        fn crust_hashmap_str_i64_contains(map: &Map, key: &OwnedStr) -> bool;
+        fn crust_hashmap_str_i64_insert(map: &mut Map, key: &mut OwnedStr, value: &mut i64)
+            -> bool;
+        fn crust_hashmap_str_i64_remove(map: &mut Map, key: &OwnedStr, out_value: *mut i64)
+            -> bool;
+        fn crust_hashmap_str_i64_next<'a>(
+            map: &'a Map,
+            cursor: &mut usize,
+        ) -> Option<&'a KeyValue<OwnedStr, i64>>;
+        fn crust_hashmap_str_i64_free(map: &mut Map);
+        fn crust_hashmap_str_str_remove(
+            map: &mut HashMap<OwnedStr, OwnedStr>,
+            key: &OwnedStr,
+            out_value: *mut OwnedStr,
+        ) -> bool;
+    }
+
+    #[test]
+    fn export_hashmap_functions() {
+        let mut storage = MaybeUninit::<Map>::uninit();
+        // SAFETY: the functions are generated by `export_hashmap!` with the
+        // signatures declared above, and `init` initializes `storage`.
+        unsafe {
+            crust_hashmap_str_i64_init(storage.as_mut_ptr());
+            let map = storage.assume_init_mut();
+            for i in 0..10 {
+                let mut key: OwnedStr = format!("k{i}").into();
+                let mut value = i;
+                assert!(crust_hashmap_str_i64_insert(map, &mut key, &mut value));
+                assert!(key.is_empty());
+            }
+            let key: OwnedStr = "k3".to_string().into();
+            let mut value = 30;
+            assert!(!crust_hashmap_str_i64_insert(
+                map,
+                &mut key.clone(),
+                &mut value
+            ));
+            assert_eq!(crust_hashmap_str_i64_len(map), 10);
+            assert_eq!(crust_hashmap_str_i64_get(map, &key), Some(&30));
+            assert!(crust_hashmap_str_i64_contains(map, &key));
+
+            let mut removed = 0;
+            assert!(crust_hashmap_str_i64_remove(map, &key, &mut removed));
+            assert_eq!(removed, 30);
+            assert!(!crust_hashmap_str_i64_remove(map, &key, ptr::null_mut()));
+            assert_eq!(crust_hashmap_str_i64_get(map, &key), None);
+
+            let mut cursor = 0;
+            let mut sum = 0;
+            while let Some(entry) = crust_hashmap_str_i64_next(map, &mut cursor) {
+                sum += entry.value;
+            }
+            assert_eq!(sum, 45 - 3);
+            assert_eq!(crust_hashmap_str_i64_next(map, &mut cursor), None);
+
+            crust_hashmap_str_i64_free(map);
+            assert!(map.slots().is_empty());
+            ptr::drop_in_place(map);
+        }
+    }
+
+    #[test]
+    fn export_hashmap_remove_into_uninit() {
+        let mut map = HashMap::new();
+        let key: OwnedStr = "key".to_string().into();
+        map.insert(key.clone(), OwnedStr::from("value".to_string()));
+        let mut removed = MaybeUninit::<OwnedStr>::uninit();
+        // SAFETY: `removed` is valid for writes, and `remove` returning true
+        // means that it was initialized.
+        unsafe {
+            assert!(crust_hashmap_str_str_remove(
+                &mut map,
+                &key,
+                removed.as_mut_ptr()
+            ));
+            assert_eq!(&*removed.assume_init(), "value");
+        }
+        assert!(map.is_empty());
+    }
+}
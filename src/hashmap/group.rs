@@ -0,0 +1,273 @@
+#![allow(unsafe_code)]
+
+//! Group-wise scanning of control bytes.
+//!
+//! A group is `WIDTH` consecutive control bytes that are compared in one go.
+//! With SSE2 a group is 16 bytes wide and matched with vector compares,
+//! otherwise it is 8 bytes wide and matched with bit tricks on a `u64`.
+
+/// The control byte of a slot that has never been used.
+pub(crate) const EMPTY: u8 = 0b1111_1111;
+
+/// The control byte of a slot whose entry was removed. Probing continues
+/// past it.
+pub(crate) const DELETED: u8 = 0b1000_0000;
+
+/// Returns `true` if the control byte belongs to an occupied slot, which
+/// holds the 7-bit H2 tag of its hash.
+#[inline]
+pub(crate) fn is_full(ctrl: u8) -> bool {
+    ctrl & 0x80 == 0
+}
+
+/// The H1 part of a hash: the home slot in a table of `bucket_mask + 1`
+/// slots. It takes the low bits, which relies on the hasher mixing its
+/// state in `finish`, as [`FxHasher`](crate::fxhash::FxHasher) does.
+#[inline]
+pub(crate) fn h1(hash: u64, bucket_mask: usize) -> usize {
+    hash as usize & bucket_mask
+}
+
+/// The H2 tag of a hash: its top 7 bits.
+#[inline]
+pub(crate) fn h2(hash: u64) -> u8 {
+    (hash >> (64 - 7)) as u8
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+mod sse2 {
+    use std::arch::x86_64::{
+        __m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_set1_epi8,
+    };
+
+    use super::EMPTY;
+
+    /// A set of matching bytes in a group.
+    #[derive(Clone, Copy)]
+    pub(crate) struct BitMask(u16);
+
+    impl BitMask {
+        #[inline]
+        pub(crate) fn any_bit_set(self) -> bool {
+            self.0 != 0
+        }
+
+        /// Returns the index of the first matching byte of the group.
+        #[inline]
+        pub(crate) fn lowest_set_bit(self) -> Option<usize> {
+            if self.0 == 0 {
+                None
+            } else {
+                Some(self.trailing_zeros())
+            }
+        }
+
+        /// Returns the number of unmatched bytes at the start of the group.
+        #[inline]
+        pub(crate) fn trailing_zeros(self) -> usize {
+            self.0.trailing_zeros() as usize
+        }
+
+        /// Returns the number of unmatched bytes at the end of the group.
+        #[inline]
+        pub(crate) fn leading_zeros(self) -> usize {
+            self.0.leading_zeros() as usize
+        }
+    }
+
+    impl Iterator for BitMask {
+        type Item = usize;
+
+        #[inline]
+        fn next(&mut self) -> Option<usize> {
+            let bit = self.lowest_set_bit()?;
+            self.0 &= self.0 - 1;
+            Some(bit)
+        }
+    }
+
+    /// Sixteen control bytes, matched with SSE2.
+    #[derive(Clone, Copy)]
+    pub(crate) struct Group(__m128i);
+
+    impl Group {
+        pub(crate) const WIDTH: usize = 16;
+
+        /// Loads the group starting at `ctrl[0]`.
+        #[inline]
+        pub(crate) fn load(ctrl: &[u8]) -> Self {
+            let bytes: &[u8; Self::WIDTH] = ctrl[..Self::WIDTH]
+                .try_into()
+                .unwrap_or_else(|_| unreachable!("Group should be complete."));
+            // SAFETY: `bytes` is valid for a 16 byte read, and the load does
+            // not require alignment. SSE2 is enabled for this target.
+            unsafe { Group(_mm_loadu_si128(bytes.as_ptr().cast())) }
+        }
+
+        /// Returns the bytes of the group that are equal to `byte`.
+        #[inline]
+        pub(crate) fn match_byte(self, byte: u8) -> BitMask {
+            // SAFETY: SSE2 is enabled for this target.
+            unsafe {
+                let cmp = _mm_cmpeq_epi8(self.0, _mm_set1_epi8(byte as i8));
+                BitMask(_mm_movemask_epi8(cmp) as u16)
+            }
+        }
+
+        /// Returns the bytes of the group that are `EMPTY`.
+        #[inline]
+        pub(crate) fn match_empty(self) -> BitMask {
+            self.match_byte(EMPTY)
+        }
+
+        /// Returns the bytes of the group that are `EMPTY` or `DELETED`.
+        #[inline]
+        pub(crate) fn match_empty_or_deleted(self) -> BitMask {
+            // SAFETY: SSE2 is enabled for this target.
+            unsafe { BitMask(_mm_movemask_epi8(self.0) as u16) }
+        }
+    }
+}
+
+#[cfg(any(test, not(all(target_arch = "x86_64", target_feature = "sse2"))))]
+mod generic {
+    /// A set of matching bytes in a group.
+    #[derive(Clone, Copy)]
+    pub(crate) struct BitMask(u64);
+
+    impl BitMask {
+        #[inline]
+        pub(crate) fn any_bit_set(self) -> bool {
+            self.0 != 0
+        }
+
+        /// Returns the index of the first matching byte of the group.
+        #[inline]
+        pub(crate) fn lowest_set_bit(self) -> Option<usize> {
+            if self.0 == 0 {
+                None
+            } else {
+                Some(self.trailing_zeros())
+            }
+        }
+
+        /// Returns the number of unmatched bytes at the start of the group.
+        #[inline]
+        pub(crate) fn trailing_zeros(self) -> usize {
+            self.0.trailing_zeros() as usize / 8
+        }
+
+        /// Returns the number of unmatched bytes at the end of the group.
+        #[inline]
+        pub(crate) fn leading_zeros(self) -> usize {
+            self.0.leading_zeros() as usize / 8
+        }
+    }
+
+    impl Iterator for BitMask {
+        type Item = usize;
+
+        #[inline]
+        fn next(&mut self) -> Option<usize> {
+            let bit = self.lowest_set_bit()?;
+            self.0 &= self.0 - 1;
+            Some(bit)
+        }
+    }
+
+    #[inline]
+    const fn repeat(byte: u8) -> u64 {
+        u64::from_ne_bytes([byte; Group::WIDTH])
+    }
+
+    /// Eight control bytes, matched with bit tricks on a `u64`. The bytes are
+    /// read in little-endian order so that bit `8 * i` belongs to byte `i`.
+    #[derive(Clone, Copy)]
+    pub(crate) struct Group(u64);
+
+    impl Group {
+        pub(crate) const WIDTH: usize = 8;
+
+        /// Loads the group starting at `ctrl[0]`.
+        #[inline]
+        pub(crate) fn load(ctrl: &[u8]) -> Self {
+            let bytes: [u8; Self::WIDTH] = ctrl[..Self::WIDTH]
+                .try_into()
+                .unwrap_or_else(|_| unreachable!("Group should be complete."));
+            Group(u64::from_le_bytes(bytes))
+        }
+
+        /// Returns the bytes of the group that are equal to `byte`.
+        ///
+        /// This may report false positives, which are harmless because every
+        /// match is confirmed by comparing keys.
+        #[inline]
+        pub(crate) fn match_byte(self, byte: u8) -> BitMask {
+            let cmp = self.0 ^ repeat(byte);
+            BitMask(cmp.wrapping_sub(repeat(0x01)) & !cmp & repeat(0x80))
+        }
+
+        /// Returns the bytes of the group that are `EMPTY`, which are the only
+        /// control bytes with both of their top two bits set.
+        #[inline]
+        pub(crate) fn match_empty(self) -> BitMask {
+            BitMask(self.0 & (self.0 << 1) & repeat(0x80))
+        }
+
+        /// Returns the bytes of the group that are `EMPTY` or `DELETED`.
+        #[inline]
+        pub(crate) fn match_empty_or_deleted(self) -> BitMask {
+            BitMask(self.0 & repeat(0x80))
+        }
+    }
+}
+
+#[cfg(not(all(target_arch = "x86_64", target_feature = "sse2")))]
+pub(crate) use generic::Group;
+#[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+pub(crate) use sse2::Group;
+
+#[cfg(test)]
+mod tests {
+    use super::{h2, is_full, DELETED, EMPTY};
+
+    macro_rules! group_match_test {
+        ($name:ident, $group:ty) => {
+            #[test]
+            fn $name() {
+                const WIDTH: usize = <$group>::WIDTH;
+                let mut ctrl = vec![EMPTY; WIDTH];
+                ctrl[1] = 0x12;
+                ctrl[2] = DELETED;
+                ctrl[3] = 0x12;
+                ctrl[5] = 0x7f;
+                let group = <$group>::load(&ctrl);
+                assert_eq!(group.match_byte(0x12).collect::<Vec<_>>(), vec![1, 3]);
+                assert_eq!(group.match_byte(0x7f).collect::<Vec<_>>(), vec![5]);
+                let empty: Vec<usize> = group.match_empty().collect();
+                assert_eq!(empty[..2], [0, 4]);
+                assert_eq!(empty.len(), WIDTH - 4);
+                assert_eq!(group.match_empty_or_deleted().nth(1), Some(2));
+                assert_eq!(group.match_empty().trailing_zeros(), 0);
+                assert_eq!(group.match_empty().leading_zeros(), 0);
+
+                let full = <$group>::load(&[0u8; 16]);
+                assert!(!full.match_empty().any_bit_set());
+                assert_eq!(full.match_empty().leading_zeros(), WIDTH);
+                assert_eq!(full.match_byte(0).count(), WIDTH);
+            }
+        };
+    }
+
+    group_match_test!(group_match_generic, super::generic::Group);
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    group_match_test!(group_match_sse2, super::sse2::Group);
+
+    #[test]
+    fn group_tags() {
+        assert!(is_full(h2(u64::MAX)));
+        assert_eq!(h2(u64::MAX), 0x7f);
+        assert!(!is_full(EMPTY));
+        assert!(!is_full(DELETED));
+    }
+}
@@ -0,0 +1,1125 @@
+//! A Robin Hood hashing variant of [`HashMap`](super::HashMap).
+//!
+//! Every slot records how far its entry sits from its home slot. When an
+//! insert probes past an entry that is closer to its home than the entry
+//! being inserted, the two swap places and the displaced entry continues
+//! probing. This keeps probe lengths short and even at high load factors,
+//! and a lookup for a missing key can stop at the first slot whose entry is
+//! closer to its home than the current probe distance.
+
+use serde::de::{MapAccess, Visitor};
+use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::Index;
+use std::{fmt, mem};
+
+use super::{
+    cautious_capacity, empty_slots, group, hash_unordered, try_empty_slots, DefaultHashBuilder,
+    IntoIter, IntoKeys, IntoValues, Iter, IterMut, KeyValue, Keys, TryReserveError, Values,
+    ValuesMut,
+};
+use crate::{option::Option, owned_slice::OwnedSlice};
+
+/// A hash map using Robin Hood hashing, with a layout that cbindgen can
+/// describe.
+///
+/// It offers the same API as [`HashMap`](super::HashMap) and can be filled
+/// up to a load factor of 7/8. See the [module documentation](self) for how
+/// it differs. Like `HashMap`, two maps are equal if they hold the same
+/// entries.
+#[derive(Clone)]
+#[repr(C)]
+pub struct RobinHoodMap<K, V, S = DefaultHashBuilder> {
+    // The number of slots is zero or a power of two.
+    data: OwnedSlice<Option<KeyValue<K, V>>>,
+    // The probe distance of the entry in each slot plus one, or zero if the
+    // slot is empty.
+    distances: OwnedSlice<u32>,
+    n_items: usize,
+    // The number of slots minus one, or zero if there are no slots.
+    bucket_mask: usize,
+    // The longest probe distance of any entry since the table was built.
+    // Lookups never probe further than this.
+    max_distance: u32,
+    _hasher: PhantomData<S>,
+}
+
+/// Returns the number of entries that fit into `buckets` slots.
+fn bucket_capacity(buckets: usize) -> usize {
+    buckets / 8 * 7
+}
+
+/// Returns the number of slots needed to hold `capacity` entries without
+/// exceeding the maximum load factor of 7/8, or `None` if it overflows.
+fn capacity_to_buckets(capacity: usize) -> std::option::Option<usize> {
+    if capacity == 0 {
+        return Some(0);
+    }
+    capacity
+        .checked_mul(8)
+        .map(|adjusted| adjusted.div_ceil(7).max(8))
+        .and_then(usize::checked_next_power_of_two)
+}
+
+/// Allocates the probe distances of `buckets` empty slots, reporting
+/// allocation failure instead of aborting.
+fn try_empty_distances(buckets: usize) -> Result<OwnedSlice<u32>, TryReserveError> {
+    let mut distances = Vec::new();
+    distances.try_reserve_exact(buckets)?;
+    distances.resize(buckets, 0);
+    Ok(distances.into())
+}
+
+impl<K, V> RobinHoodMap<K, V> {
+    /// Creates an empty map. It does not allocate until the first insert.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates an empty map that can hold at least `capacity` entries
+    /// without reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::allocate(capacity)
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> RobinHoodMap<K, V, S> {
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, `None` is returned. If the
+    /// map did have this key present, the value is updated and the old value
+    /// is returned. The key is not updated.
+    pub fn insert(&mut self, key: K, value: V) -> std::option::Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    /// Gets the given key's corresponding entry in the map for in-place
+    /// manipulation.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        self.reserve(1);
+        let hash = self.hash(&key);
+        match self.probe(hash, &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => Entry::Vacant(VacantEntry {
+                map: self,
+                key,
+                hash,
+            }),
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    pub fn get<Q>(&self, key: &Q) -> std::option::Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        Some(&self.entry_at(index).value)
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    pub fn get_key_value<Q>(&self, key: &Q) -> std::option::Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        let entry = self.entry_at(index);
+        Some((&entry.key, &entry.value))
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> std::option::Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        Some(&mut self.entry_at_mut(index).value)
+    }
+
+    /// Attempts to get mutable references to `N` values in the map at once.
+    ///
+    /// Returns `None` if any of the keys is missing, or if two keys refer to
+    /// the same entry.
+    pub fn get_many_mut<Q, const N: usize>(
+        &mut self,
+        keys: [&Q; N],
+    ) -> std::option::Option<[&mut V; N]>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut indices = [0; N];
+        for (index, key) in indices.iter_mut().zip(keys) {
+            *index = self.find(key)?;
+        }
+        let slots = self.data.get_disjoint_mut(indices).ok()?;
+        Some(slots.map(|slot| match slot {
+            Option::Some(entry) => &mut entry.value,
+            Option::None => unreachable!("Slot should be occupied."),
+        }))
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    pub fn remove<Q>(&mut self, key: &Q) -> std::option::Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> std::option::Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        let entry = self.remove_slot(index);
+        Some((entry.key, entry.value))
+    }
+
+    /// Retains only the elements specified by the predicate.
+    ///
+    /// In other words, removes all pairs `(k, v)` for which `f(&k, &mut v)`
+    /// returns `false`.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        if self.n_items == 0 {
+            return;
+        }
+        // Removing an entry shifts the entries after it back by one slot.
+        // The walk goes backwards from a slot whose entry, if any, is in its
+        // home slot, so it only ever shifts entries that were already
+        // visited, and every entry is passed to `f` exactly once.
+        let start = self
+            .distances
+            .iter()
+            .position(|&distance| distance <= 1)
+            .unwrap_or_else(|| unreachable!("The table should have an empty slot."));
+        let mut index = start;
+        for _ in 0..self.data.len() {
+            index = index.wrapping_sub(1) & self.bucket_mask;
+            let keep = match &mut self.data[index] {
+                Option::Some(entry) => f(&entry.key, &mut entry.value),
+                Option::None => true,
+            };
+            if !keep {
+                self.remove_slot(index);
+            }
+        }
+    }
+
+    /// Reserves capacity for at least `additional` more entries to be
+    /// inserted without reallocating.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new number of slots overflows `usize`.
+    pub fn reserve(&mut self, additional: usize) {
+        if additional <= self.capacity() - self.n_items {
+            return;
+        }
+        let buckets = self
+            .reserve_buckets(additional)
+            .expect("Hash table capacity overflow.");
+        self.resize(buckets);
+    }
+
+    /// Tries to reserve capacity for at least `additional` more entries to be
+    /// inserted without reallocating.
+    ///
+    /// Unlike [`reserve`](RobinHoodMap::reserve), this returns an error
+    /// instead of panicking or aborting if the capacity overflows or the
+    /// allocation fails. The map is unchanged in that case.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if additional <= self.capacity() - self.n_items {
+            return Ok(());
+        }
+        let buckets = self
+            .reserve_buckets(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        let data = try_empty_slots(buckets)?;
+        let distances = try_empty_distances(buckets)?;
+        self.rebuild(data, distances);
+        Ok(())
+    }
+
+    /// Shrinks the capacity of the map as much as possible while keeping the
+    /// load factor. An empty map releases its allocation.
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    /// Shrinks the capacity of the map with a lower limit. The capacity
+    /// stays at least as large as both the length and `min_capacity`.
+    ///
+    /// If the current capacity is less than the lower limit, this is a no-op.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let buckets = capacity_to_buckets(self.n_items.max(min_capacity)).unwrap_or(usize::MAX);
+        if buckets < self.data.len() {
+            self.resize(buckets);
+        }
+    }
+
+    #[inline]
+    fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        S::default().hash_one(key)
+    }
+
+    /// Returns the index of the slot holding `key`.
+    #[inline]
+    fn find<Q>(&self, key: &Q) -> std::option::Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.n_items == 0 {
+            return None;
+        }
+        self.probe(self.hash(key), key)
+    }
+
+    /// Looks up the slot of `key` in a table that has slots.
+    ///
+    /// The probe stops at an empty slot, at a slot whose entry is closer to
+    /// its home than the current probe distance, or after `max_distance`
+    /// steps. Keys are only compared for entries with the same probe
+    /// distance, which are the only ones that can share the home slot.
+    fn probe<Q>(&self, hash: u64, key: &Q) -> std::option::Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let mut index = group::h1(hash, self.bucket_mask);
+        for distance in 1..=self.max_distance + 1 {
+            let resident = self.distances[index];
+            if resident < distance {
+                return None;
+            }
+            if resident == distance && self.entry_at(index).key.borrow() == key {
+                return Some(index);
+            }
+            index = (index + 1) & self.bucket_mask;
+        }
+        None
+    }
+
+    #[inline]
+    fn entry_at(&self, index: usize) -> &KeyValue<K, V> {
+        self.data[index]
+            .as_ref()
+            .unwrap_or_else(|| unreachable!("Slot should be occupied."))
+    }
+
+    #[inline]
+    fn entry_at_mut(&mut self, index: usize) -> &mut KeyValue<K, V> {
+        self.data[index]
+            .as_mut()
+            .unwrap_or_else(|| unreachable!("Slot should be occupied."))
+    }
+
+    /// Inserts an entry whose key is not in the map yet, and returns the
+    /// index of its slot.
+    ///
+    /// Whenever the entry being placed is further from its home than the
+    /// resident of a slot, it takes the slot and the resident is placed
+    /// further along instead.
+    fn insert_new(&mut self, hash: u64, entry: KeyValue<K, V>) -> usize {
+        let mut entry = Option::Some(entry);
+        let mut distance = 1;
+        let mut index = group::h1(hash, self.bucket_mask);
+        let mut inserted = None;
+        loop {
+            let resident = self.distances[index];
+            if resident < distance {
+                mem::swap(&mut self.data[index], &mut entry);
+                self.distances[index] = distance;
+                self.max_distance = self.max_distance.max(distance - 1);
+                let index = *inserted.get_or_insert(index);
+                if resident == 0 {
+                    self.n_items += 1;
+                    return index;
+                }
+                distance = resident;
+            }
+            index = (index + 1) & self.bucket_mask;
+            distance += 1;
+        }
+    }
+
+    /// Takes the entry out of the slot at `index`.
+    ///
+    /// Uses backward-shift deletion: the following entries that are not in
+    /// their home slot move back by one, so no tombstones are needed and the
+    /// probe distances stay ordered.
+    fn remove_slot(&mut self, index: usize) -> KeyValue<K, V> {
+        let entry = mem::replace(&mut self.data[index], Option::None)
+            .unwrap_or_else(|| unreachable!("Removed slot should be occupied."));
+        self.distances[index] = 0;
+        self.n_items -= 1;
+        let mut hole = index;
+        let mut next = (index + 1) & self.bucket_mask;
+        while self.distances[next] > 1 {
+            self.data.swap(hole, next);
+            self.distances[hole] = self.distances[next] - 1;
+            self.distances[next] = 0;
+            hole = next;
+            next = (next + 1) & self.bucket_mask;
+        }
+        entry
+    }
+
+    /// Returns the number of slots needed so that `additional` more entries
+    /// fit, or `None` if it overflows.
+    fn reserve_buckets(&self, additional: usize) -> std::option::Option<usize> {
+        capacity_to_buckets(self.n_items.checked_add(additional)?)
+    }
+
+    /// Rebuilds the table with `buckets` slots, moving every entry into its
+    /// new slot.
+    fn resize(&mut self, buckets: usize) {
+        self.rebuild(empty_slots(buckets), vec![0; buckets].into());
+    }
+
+    /// Moves every entry into `data`, whose probe distances are `distances`.
+    /// Both must be empty and sized for the same number of slots.
+    fn rebuild(&mut self, data: OwnedSlice<Option<KeyValue<K, V>>>, distances: OwnedSlice<u32>) {
+        let buckets = data.len();
+        debug_assert!(buckets == 0 || buckets.is_power_of_two());
+        let old_data = mem::replace(&mut self.data, data);
+        self.distances = distances;
+        self.bucket_mask = buckets.saturating_sub(1);
+        self.max_distance = 0;
+        self.n_items = 0;
+        for entry in old_data
+            .into_iter()
+            .filter_map(Into::<std::option::Option<KeyValue<K, V>>>::into)
+        {
+            let hash = self.hash(&entry.key);
+            self.insert_new(hash, entry);
+        }
+    }
+}
+
+impl<K, V, S> RobinHoodMap<K, V, S> {
+    /// Creates an empty map that can hold at least `capacity` entries
+    /// without reallocating, hashing with `S`.
+    fn allocate(capacity: usize) -> Self {
+        let buckets = capacity_to_buckets(capacity).expect("Hash table capacity overflow.");
+        RobinHoodMap {
+            data: empty_slots(buckets),
+            distances: vec![0; buckets].into(),
+            n_items: 0,
+            bucket_mask: buckets.saturating_sub(1),
+            max_distance: 0,
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Returns the number of entries the map can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        bucket_capacity(self.data.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.n_items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n_items == 0
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            slots: self.data.iter(),
+            remaining: self.n_items,
+        }
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order, with
+    /// mutable references to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            slots: self.data.iter_mut(),
+            remaining: self.n_items,
+        }
+    }
+
+    /// An iterator visiting all keys in arbitrary order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// An iterator visiting all values in arbitrary order.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    /// An iterator visiting all values mutably in arbitrary order.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// Creates a consuming iterator visiting all the keys in arbitrary order.
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys {
+            inner: self.into_iter(),
+        }
+    }
+
+    /// Creates a consuming iterator visiting all the values in arbitrary
+    /// order.
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues {
+            inner: self.into_iter(),
+        }
+    }
+
+    /// The raw slot array of the table, including the empty slots.
+    pub fn slots(&self) -> &[Option<KeyValue<K, V>>] {
+        &self.data
+    }
+
+    /// The longest probe distance of any entry since the table was last
+    /// rebuilt.
+    pub fn max_probe_distance(&self) -> usize {
+        self.max_distance as usize
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for RobinHoodMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S> Default for RobinHoodMap<K, V, S> {
+    fn default() -> Self {
+        Self::allocate(0)
+    }
+}
+
+impl<K: Hash + Eq, V: PartialEq, S: BuildHasher + Default> PartialEq for RobinHoodMap<K, V, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K: Hash + Eq, V: Eq, S: BuildHasher + Default> Eq for RobinHoodMap<K, V, S> {}
+
+impl<K: Hash + Eq, V: Hash, S: BuildHasher + Default> Hash for RobinHoodMap<K, V, S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_unordered::<_, _, S, _>(self.iter(), state);
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default, Q> Index<&Q> for RobinHoodMap<K, V, S>
+where
+    K: Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `RobinHoodMap`.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("Key not found in RobinHoodMap.")
+    }
+}
+
+/// A view into a single entry in a map, which may either be vacant or
+/// occupied.
+///
+/// This enum is constructed from the [`entry`](RobinHoodMap::entry) method
+/// on [`RobinHoodMap`].
+pub enum Entry<'a, K, V, S = DefaultHashBuilder> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+/// A view into an occupied entry in a [`RobinHoodMap`]. It is part of the
+/// [`Entry`] enum.
+pub struct OccupiedEntry<'a, K, V, S = DefaultHashBuilder> {
+    map: &'a mut RobinHoodMap<K, V, S>,
+    index: usize,
+}
+
+/// A view into a vacant entry in a [`RobinHoodMap`]. It is part of the
+/// [`Entry`] enum.
+pub struct VacantEntry<'a, K, V, S = DefaultHashBuilder> {
+    map: &'a mut RobinHoodMap<K, V, S>,
+    key: K,
+    hash: u64,
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher + Default> Entry<'a, K, V, S> {
+    /// Ensures a value is in the entry by inserting the default if empty, and
+    /// returns a mutable reference to the value in the entry.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default
+    /// function if empty, and returns a mutable reference to the value in the
+    /// entry.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default
+    /// function, which is given the key, if empty.
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if
+    /// empty, and returns a mutable reference to the value in the entry.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any
+    /// potential inserts into the map.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+
+    /// Returns a reference to this entry's key.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher + Default> OccupiedEntry<'a, K, V, S> {
+    /// Gets a reference to the key in the entry.
+    pub fn key(&self) -> &K {
+        &self.map.entry_at(self.index).key
+    }
+
+    /// Gets a reference to the value in the entry.
+    pub fn get(&self) -> &V {
+        &self.map.entry_at(self.index).value
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.entry_at_mut(self.index).value
+    }
+
+    /// Converts the entry into a mutable reference to the value in the entry
+    /// with a lifetime bound to the map itself.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.entry_at_mut(self.index).value
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Takes the value out of the entry, and returns it.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Takes the ownership of the key and value from the map.
+    pub fn remove_entry(self) -> (K, V) {
+        let entry = self.map.remove_slot(self.index);
+        (entry.key, entry.value)
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher + Default> VacantEntry<'a, K, V, S> {
+    /// Gets a reference to the key that would be used when inserting a value
+    /// through the `VacantEntry`.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Take ownership of the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Sets the value of the entry with the `VacantEntry`'s key, and returns a
+    /// mutable reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let entry = KeyValue {
+            key: self.key,
+            value,
+        };
+        let index = self.map.insert_new(self.hash, entry);
+        &mut self.map.entry_at_mut(index).value
+    }
+}
+
+impl<K, V, S> IntoIterator for RobinHoodMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            slots: self.data.into_vec().into_iter(),
+            remaining: self.n_items,
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a RobinHoodMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut RobinHoodMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> FromIterator<(K, V)> for RobinHoodMap<K, V, S> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = RobinHoodMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> Extend<(K, V)> for RobinHoodMap<K, V, S> {
+    /// Inserts all key-value pairs of the iterator, growing the table once
+    /// up front based on the size hint.
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        let iter = iter.into_iter();
+        let additional = if self.is_empty() {
+            iter.size_hint().0
+        } else {
+            iter.size_hint().0.div_ceil(2)
+        };
+        self.reserve(additional);
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq + Copy, V: Copy, S: BuildHasher + Default> Extend<(&'a K, &'a V)>
+    for RobinHoodMap<K, V, S>
+{
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    }
+}
+
+impl<K: Hash + Eq, V, const N: usize> From<[(K, V); N]> for RobinHoodMap<K, V> {
+    fn from(entries: [(K, V); N]) -> Self {
+        RobinHoodMap::from_iter(entries)
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default, T> From<std::collections::HashMap<K, V, T>>
+    for RobinHoodMap<K, V, S>
+{
+    fn from(map: std::collections::HashMap<K, V, T>) -> Self {
+        RobinHoodMap::from_iter(map)
+    }
+}
+
+impl<K: Hash + Eq, V, S> From<RobinHoodMap<K, V, S>> for std::collections::HashMap<K, V> {
+    fn from(map: RobinHoodMap<K, V, S>) -> Self {
+        std::collections::HashMap::from_iter(map)
+    }
+}
+
+impl<K, V, H> Serialize for RobinHoodMap<K, V, H>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+struct MapVisitor<K, V, S> {
+    #[allow(clippy::type_complexity)]
+    marker: PhantomData<fn() -> RobinHoodMap<K, V, S>>,
+}
+
+impl<'de, K, V, S> Visitor<'de> for MapVisitor<K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    type Value = RobinHoodMap<K, V, S>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        let mut map = RobinHoodMap::allocate(cautious_capacity(access.size_hint()));
+
+        while let Some((key, value)) = access.next_entry()? {
+            map.insert(key, value);
+        }
+
+        Ok(map)
+    }
+}
+
+impl<'de, K, V, S> Deserialize<'de> for RobinHoodMap<K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(MapVisitor {
+            marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{Hash, Hasher};
+
+    use crate::owned_str::OwnedStr;
+
+    use super::{Entry, RobinHoodMap, TryReserveError};
+
+    /// A key whose hash only depends on `group`, to force collisions.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct Collide {
+        group: u32,
+        id: u32,
+    }
+
+    impl Hash for Collide {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.group.hash(state)
+        }
+    }
+
+    #[test]
+    fn robin_hood_insert_get_remove() {
+        let mut map = RobinHoodMap::new();
+        let key1: OwnedStr = "test1".to_string().into();
+        let key2: OwnedStr = "test2".to_string().into();
+        assert_eq!(map.insert(key1.clone(), 8), None);
+        assert_eq!(map.insert(key2.clone(), 16), None);
+        assert_eq!(map.insert(key1.clone(), 9), Some(8));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&key1], 9);
+        assert_eq!(map.remove(&key1), Some(9));
+        assert_eq!(map.get(&key1), None);
+        assert_eq!(map.get_key_value(&key2), Some((&key2, &16)));
+        *map.get_mut(&key2).expect("Failed to get value.") += 1;
+        assert_eq!(map[&key2], 17);
+    }
+
+    #[test]
+    fn robin_hood_collisions() {
+        let mut map = RobinHoodMap::with_capacity(64);
+        for id in 0..48 {
+            map.insert(Collide { group: id % 3, id }, id);
+        }
+        for id in (0..48).step_by(5) {
+            assert_eq!(map.remove(&Collide { group: id % 3, id }), Some(id));
+        }
+        for id in 0..48 {
+            let expected = if id % 5 == 0 { None } else { Some(&id) };
+            assert_eq!(map.get(&Collide { group: id % 3, id }), expected);
+        }
+        assert_eq!(map.get(&Collide { group: 3, id: 0 }), None);
+    }
+
+    #[test]
+    fn robin_hood_high_load() {
+        let mut map = RobinHoodMap::with_capacity(7000);
+        let buckets = map.slots().len();
+        for i in 0..map.capacity() {
+            map.insert(i, i);
+        }
+        assert_eq!(map.slots().len(), buckets);
+        assert!(map.len() * 8 >= buckets * 7);
+        for i in 0..map.capacity() {
+            assert_eq!(map[&i], i);
+        }
+        assert_eq!(map.get(&usize::MAX), None);
+        assert!(map.max_probe_distance() < 64);
+    }
+
+    #[test]
+    fn robin_hood_aligned_keys() {
+        let mut map = RobinHoodMap::new();
+        for i in 0..4096u64 {
+            map.insert(i << 20, i);
+        }
+        assert!(map.max_probe_distance() <= 16);
+        assert_eq!(map[&(4095 << 20)], 4095);
+    }
+
+    #[test]
+    fn robin_hood_entry_iter_retain() {
+        let mut map: RobinHoodMap<OwnedStr, usize> = RobinHoodMap::new();
+        for word in "a b c a b a".split(' ') {
+            *map.entry(word.to_string().into()).or_insert(0) += 1;
+        }
+        assert_eq!(map[&OwnedStr::from("a".to_string())], 3);
+        match map.entry("c".to_string().into()) {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), 1),
+            Entry::Vacant(_) => panic!("Entry should be occupied."),
+        }
+        let mut entries: Vec<(String, usize)> = map
+            .iter()
+            .map(|(key, value)| (key.to_string(), *value))
+            .collect();
+        entries.sort();
+        assert_eq!(entries, vec![("a".to_string(), 3), ("b".to_string(), 2)]);
+
+        let mut map: RobinHoodMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+        map.retain(|key, _| key % 2 == 0);
+        assert_eq!(map.len(), 50);
+        assert_eq!(map.get(&3), None);
+        assert_eq!(map.get(&4), Some(&4));
+    }
+
+    #[test]
+    fn robin_hood_retain_keeps_probe_chains() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        // Long runs of colliding keys, one of which wraps around the end of
+        // the table.
+        let mut map = RobinHoodMap::with_capacity(56);
+        for id in 0..56 {
+            map.insert(Collide { group: id % 10, id }, id);
+        }
+        let mut calls = 0;
+        map.retain(|key, _| {
+            calls += 1;
+            key.id % 3 != 0
+        });
+        assert_eq!(calls, 56);
+        assert_eq!(map.len(), 37);
+        for id in 0..56 {
+            let expected = if id % 3 == 0 { None } else { Some(&id) };
+            assert_eq!(map.get(&Collide { group: id % 10, id }), expected);
+        }
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            map.retain(|key, _| {
+                assert_ne!(key.id, 25);
+                key.id % 2 == 0
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(map.iter().count(), map.len());
+        for (key, value) in map.clone() {
+            assert_eq!(map.get(&key), Some(&value));
+        }
+        assert!(map.get(&Collide { group: 5, id: 25 }).is_some());
+    }
+
+    #[test]
+    fn robin_hood_reserve_and_shrink() {
+        let mut map: RobinHoodMap<i32, i32> = RobinHoodMap::new();
+        map.reserve(100);
+        assert!(map.capacity() >= 100);
+        let buckets = map.slots().len();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.slots().len(), buckets);
+
+        map.retain(|key, _| *key < 10);
+        map.shrink_to(50);
+        assert!(map.capacity() >= 50);
+        assert!(map.slots().len() < buckets);
+        map.shrink_to_fit();
+        assert_eq!(map.slots().len(), 16);
+        for i in 0..10 {
+            assert_eq!(map[&i], i);
+        }
+
+        map.retain(|_, _| false);
+        map.shrink_to_fit();
+        assert!(map.slots().is_empty());
+        map.insert(1, 1);
+        assert_eq!(map[&1], 1);
+    }
+
+    #[test]
+    fn robin_hood_try_reserve() {
+        let mut map: RobinHoodMap<i32, i32> = RobinHoodMap::new();
+        assert_eq!(map.try_reserve(10), Ok(()));
+        assert!(map.capacity() >= 10);
+        map.insert(1, 1);
+        assert_eq!(
+            map.try_reserve(usize::MAX),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert!(matches!(
+            map.try_reserve(usize::MAX / 2048),
+            Err(TryReserveError::AllocError(_))
+        ));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[&1], 1);
+    }
+
+    #[test]
+    fn robin_hood_std_conversion() {
+        let mut std_map = std::collections::HashMap::new();
+        std_map.insert(OwnedStr::from("a".to_string()), 1);
+        std_map.insert(OwnedStr::from("b".to_string()), 2);
+        let map: RobinHoodMap<OwnedStr, i32> = std_map.clone().into();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&OwnedStr::from("b".to_string())], 2);
+        let round_trip: std::collections::HashMap<OwnedStr, i32> = map.into();
+        assert_eq!(round_trip, std_map);
+    }
+
+    #[test]
+    fn robin_hood_matches_std() {
+        let mut map = RobinHoodMap::new();
+        let mut expected = std::collections::HashMap::new();
+        let mut state: u64 = 1;
+        for _ in 0..20000 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            let key = (state >> 33) % 512;
+            match (state >> 20) % 3 {
+                0 => assert_eq!(map.remove(&key), expected.remove(&key)),
+                _ => assert_eq!(map.insert(key, state), expected.insert(key, state)),
+            }
+            assert_eq!(map.len(), expected.len());
+        }
+        for key in 0..512 {
+            assert_eq!(map.get(&key), expected.get(&key));
+        }
+    }
+
+    #[test]
+    fn robin_hood_deserialize_caps_size_hint() {
+        use serde::de::value::{Error, MapAccessDeserializer};
+        use serde::de::{DeserializeSeed, MapAccess};
+        use serde::Deserialize;
+
+        /// An empty map that claims to hold `usize::MAX` entries.
+        struct HugeHint;
+
+        impl<'de> MapAccess<'de> for HugeHint {
+            type Error = Error;
+
+            fn next_key_seed<K: DeserializeSeed<'de>>(
+                &mut self,
+                _seed: K,
+            ) -> Result<Option<K::Value>, Error> {
+                Ok(None)
+            }
+
+            fn next_value_seed<V: DeserializeSeed<'de>>(
+                &mut self,
+                _seed: V,
+            ) -> Result<V::Value, Error> {
+                unreachable!("There are no keys.")
+            }
+
+            fn size_hint(&self) -> Option<usize> {
+                Some(usize::MAX)
+            }
+        }
+
+        let map =
+            RobinHoodMap::<u64, u64>::deserialize(MapAccessDeserializer::new(HugeHint)).unwrap();
+        assert!(map.is_empty() && map.capacity() >= 4096);
+    }
+
+    #[test]
+    fn robin_hood_serde() {
+        let record = r#"{"a": 1, "b": 2, "a": 3}"#;
+        let map: RobinHoodMap<OwnedStr, i32> = serde_json::from_str(record).unwrap();
+        assert_eq!(map.len(), 2);
+        let json = serde_json::to_string(&map).unwrap();
+        let map_two: RobinHoodMap<OwnedStr, i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(map_two[&OwnedStr::from("a".to_string())], 3);
+        assert_eq!(map_two, map);
+    }
+}
@@ -0,0 +1,595 @@
+use serde::de::{SeqAccess, Visitor};
+use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::iter::{Chain, FusedIterator};
+use std::marker::PhantomData;
+
+use crate::hashmap::{self, DefaultHashBuilder, Entry, HashMap, TryReserveError};
+
+/// A hash set with a layout that cbindgen can describe.
+///
+/// It is a [`HashMap`] with [`SetValue`] values, so it shares the table
+/// layout, hashing and probing of the map. `SetValue` takes no space, so a
+/// slot of `HashSet<T>` has the same layout as an `Option<T>`.
+///
+/// Two sets are equal if they hold the same elements, regardless of
+/// insertion order or capacity.
+#[derive(Clone)]
+#[repr(C)]
+pub struct HashSet<T, S = DefaultHashBuilder> {
+    map: HashMap<T, SetValue, S>,
+}
+
+/// The value type of the map behind a [`HashSet`].
+///
+/// It takes no space in Rust. C++ cannot express that for a field, so the
+/// header specializes `KeyValue<K, SetValue>` to hold only the key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct SetValue;
+
+impl<T> HashSet<T> {
+    /// Creates an empty set. It does not allocate until the first insert.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates an empty set that can hold at least `capacity` elements
+    /// without reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        HashSet {
+            map: HashMap::with_capacity(capacity),
+        }
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher + Default> HashSet<T, S> {
+    /// Adds a value to the set.
+    ///
+    /// Returns whether the value was newly inserted. If the set already
+    /// contained an equal value, it is not updated.
+    pub fn insert(&mut self, value: T) -> bool {
+        match self.map.entry(value) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(SetValue);
+                true
+            }
+        }
+    }
+
+    /// Adds a value to the set, replacing the existing value, if any, that
+    /// is equal to the given one. Returns the replaced value.
+    pub fn replace(&mut self, value: T) -> std::option::Option<T> {
+        let replaced = self.map.remove_entry(&value).map(|(value, _)| value);
+        self.map.insert(value, SetValue);
+        replaced
+    }
+
+    /// Returns `true` if the set contains a value.
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.contains_key(value)
+    }
+
+    /// Returns a reference to the value in the set, if any, that is equal to
+    /// the given value.
+    pub fn get<Q>(&self, value: &Q) -> std::option::Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.get_key_value(value).map(|(value, _)| value)
+    }
+
+    /// Removes a value from the set. Returns whether the value was present.
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.remove(value).is_some()
+    }
+
+    /// Removes and returns the value in the set, if any, that is equal to the
+    /// given one.
+    pub fn take<Q>(&mut self, value: &Q) -> std::option::Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.remove_entry(value).map(|(value, _)| value)
+    }
+
+    /// Retains only the elements specified by the predicate.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.map.retain(|value, _| f(value));
+    }
+
+    /// Reserves capacity for at least `additional` more elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new number of slots overflows `usize`.
+    pub fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional);
+    }
+
+    /// Tries to reserve capacity for at least `additional` more elements,
+    /// returning an error instead of panicking or aborting.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.map.try_reserve(additional)
+    }
+
+    /// Shrinks the capacity of the set as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+    }
+
+    /// Shrinks the capacity of the set with a lower limit.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.map.shrink_to(min_capacity);
+    }
+
+    /// Visits the values representing the union, i.e., all the values in
+    /// `self` or `other`, without duplicates.
+    pub fn union<'a>(&'a self, other: &'a Self) -> Union<'a, T, S> {
+        // Walk the larger set in full and only look up the smaller one.
+        let (larger, smaller) = if self.len() >= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Union {
+            iter: larger.iter().chain(smaller.difference(larger)),
+        }
+    }
+
+    /// Visits the values representing the intersection, i.e., the values
+    /// that are both in `self` and `other`.
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> Intersection<'a, T, S> {
+        let (smaller, larger) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Intersection {
+            iter: smaller.iter(),
+            other: larger,
+        }
+    }
+
+    /// Visits the values representing the difference, i.e., the values that
+    /// are in `self` but not in `other`.
+    pub fn difference<'a>(&'a self, other: &'a Self) -> Difference<'a, T, S> {
+        Difference {
+            iter: self.iter(),
+            other,
+        }
+    }
+
+    /// Visits the values representing the symmetric difference, i.e., the
+    /// values that are in `self` or in `other` but not in both.
+    pub fn symmetric_difference<'a>(&'a self, other: &'a Self) -> SymmetricDifference<'a, T, S> {
+        SymmetricDifference {
+            iter: self.difference(other).chain(other.difference(self)),
+        }
+    }
+
+    /// Returns `true` if `self` has no elements in common with `other`.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.intersection(other).next().is_none()
+    }
+
+    /// Returns `true` if every element of `self` is contained in `other`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.len() <= other.len() && self.iter().all(|value| other.contains(value))
+    }
+
+    /// Returns `true` if every element of `other` is contained in `self`.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+}
+
+impl<T, S> HashSet<T, S> {
+    /// Returns the number of elements the set can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// An iterator visiting all elements in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            inner: self.map.keys(),
+        }
+    }
+}
+
+impl<T: fmt::Debug, S> fmt::Debug for HashSet<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T, S> Default for HashSet<T, S> {
+    fn default() -> Self {
+        HashSet {
+            map: HashMap::default(),
+        }
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher + Default> PartialEq for HashSet<T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher + Default> Eq for HashSet<T, S> {}
+
+impl<T: Hash + Eq, S: BuildHasher + Default> Hash for HashSet<T, S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.map.hash(state);
+    }
+}
+
+/// An iterator over the elements of a [`HashSet`].
+///
+/// This struct is created by the [`iter`](HashSet::iter) method on
+/// [`HashSet`].
+pub struct Iter<'a, T> {
+    inner: hashmap::Keys<'a, T, SetValue>,
+}
+
+impl<T> Clone for Iter<'_, T> {
+    fn clone(&self) -> Self {
+        Iter {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> std::option::Option<&'a T> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+impl<T> FusedIterator for Iter<'_, T> {}
+
+/// An owning iterator over the elements of a [`HashSet`].
+///
+/// This struct is created by the `into_iter` method on [`HashSet`].
+pub struct IntoIter<T> {
+    inner: hashmap::IntoKeys<T, SetValue>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> std::option::Option<T> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+impl<T> FusedIterator for IntoIter<T> {}
+
+/// A lazy iterator producing the elements in the union of two
+/// [`HashSet`]s.
+///
+/// This struct is created by the [`union`](HashSet::union) method on
+/// [`HashSet`].
+pub struct Union<'a, T, S = DefaultHashBuilder> {
+    iter: Chain<Iter<'a, T>, Difference<'a, T, S>>,
+}
+
+impl<'a, T: Hash + Eq, S: BuildHasher + Default> Iterator for Union<'a, T, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> std::option::Option<&'a T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher + Default> FusedIterator for Union<'_, T, S> {}
+
+/// A lazy iterator producing the elements in the intersection of two
+/// [`HashSet`]s.
+///
+/// This struct is created by the [`intersection`](HashSet::intersection)
+/// method on [`HashSet`].
+pub struct Intersection<'a, T, S = DefaultHashBuilder> {
+    iter: Iter<'a, T>,
+    other: &'a HashSet<T, S>,
+}
+
+impl<'a, T: Hash + Eq, S: BuildHasher + Default> Iterator for Intersection<'a, T, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> std::option::Option<&'a T> {
+        let other = self.other;
+        self.iter.by_ref().find(|value| other.contains(*value))
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher + Default> FusedIterator for Intersection<'_, T, S> {}
+
+/// A lazy iterator producing the elements in the difference of two
+/// [`HashSet`]s.
+///
+/// This struct is created by the [`difference`](HashSet::difference) method
+/// on [`HashSet`].
+pub struct Difference<'a, T, S = DefaultHashBuilder> {
+    iter: Iter<'a, T>,
+    other: &'a HashSet<T, S>,
+}
+
+impl<'a, T: Hash + Eq, S: BuildHasher + Default> Iterator for Difference<'a, T, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> std::option::Option<&'a T> {
+        let other = self.other;
+        self.iter.by_ref().find(|value| !other.contains(*value))
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher + Default> FusedIterator for Difference<'_, T, S> {}
+
+/// A lazy iterator producing the elements in the symmetric difference of
+/// two [`HashSet`]s.
+///
+/// This struct is created by the
+/// [`symmetric_difference`](HashSet::symmetric_difference) method on
+/// [`HashSet`].
+pub struct SymmetricDifference<'a, T, S = DefaultHashBuilder> {
+    iter: Chain<Difference<'a, T, S>, Difference<'a, T, S>>,
+}
+
+impl<'a, T: Hash + Eq, S: BuildHasher + Default> Iterator for SymmetricDifference<'a, T, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> std::option::Option<&'a T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, std::option::Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher + Default> FusedIterator for SymmetricDifference<'_, T, S> {}
+
+impl<T, S> IntoIterator for HashSet<T, S> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter {
+            inner: self.map.into_keys(),
+        }
+    }
+}
+
+impl<'a, T, S> IntoIterator for &'a HashSet<T, S> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher + Default> FromIterator<T> for HashSet<T, S> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = HashSet::default();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher + Default> Extend<T> for HashSet<T, S> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.map
+            .extend(iter.into_iter().map(|value| (value, SetValue)));
+    }
+}
+
+impl<'a, T: Hash + Eq + Copy, S: BuildHasher + Default> Extend<&'a T> for HashSet<T, S> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+impl<T: Hash + Eq, const N: usize> From<[T; N]> for HashSet<T> {
+    fn from(values: [T; N]) -> Self {
+        HashSet::from_iter(values)
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher + Default, H> From<std::collections::HashSet<T, H>>
+    for HashSet<T, S>
+{
+    fn from(set: std::collections::HashSet<T, H>) -> Self {
+        set.into_iter().collect()
+    }
+}
+
+impl<T: Hash + Eq, S> From<HashSet<T, S>> for std::collections::HashSet<T> {
+    fn from(set: HashSet<T, S>) -> Self {
+        set.into_iter().collect()
+    }
+}
+
+impl<T, H> Serialize for HashSet<T, H>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for value in self {
+            seq.serialize_element(value)?;
+        }
+        seq.end()
+    }
+}
+
+struct SeqVisitor<T, S> {
+    marker: PhantomData<fn() -> HashSet<T, S>>,
+}
+
+impl<'de, T, S> Visitor<'de> for SeqVisitor<T, S>
+where
+    T: Deserialize<'de> + Hash + Eq,
+    S: BuildHasher + Default,
+{
+    type Value = HashSet<T, S>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut set = HashSet::default();
+        set.reserve(hashmap::cautious_capacity(access.size_hint()));
+
+        while let Some(value) = access.next_element()? {
+            set.insert(value);
+        }
+
+        Ok(set)
+    }
+}
+
+impl<'de, T, S> Deserialize<'de> for HashSet<T, S>
+where
+    T: Deserialize<'de> + Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor {
+            marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::owned_str::OwnedStr;
+
+    use super::HashSet;
+
+    fn sorted<'a>(values: impl Iterator<Item = &'a i32>) -> Vec<i32> {
+        let mut values: Vec<i32> = values.copied().collect();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn hashset_insert_contains_remove() {
+        let mut set = HashSet::new();
+        let key: OwnedStr = "a".to_string().into();
+        assert!(set.insert(key.clone()));
+        assert!(!set.insert(key.clone()));
+        assert!(set.contains(&key));
+        assert_eq!(set.get(&key), Some(&key));
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.replace(key.clone()), Some(key.clone()));
+        assert!(set.remove(&key));
+        assert!(!set.remove(&key));
+        assert!(set.is_empty());
+
+        let mut set: HashSet<i32> = (0..100).collect();
+        set.retain(|value| value % 2 == 0);
+        assert_eq!(set.len(), 50);
+        assert_eq!(set.take(&4), Some(4));
+        assert!(!set.contains(&4));
+        assert_eq!(format!("{:?}", HashSet::from([1])), "{1}");
+    }
+
+    #[test]
+    fn hashset_set_operations() {
+        let a = HashSet::from([1, 2, 3, 4]);
+        let b = HashSet::from([3, 4, 5]);
+        assert_eq!(sorted(a.union(&b)), vec![1, 2, 3, 4, 5]);
+        assert_eq!(sorted(b.union(&a)), vec![1, 2, 3, 4, 5]);
+        assert_eq!(sorted(a.intersection(&b)), vec![3, 4]);
+        assert_eq!(sorted(a.difference(&b)), vec![1, 2]);
+        assert_eq!(sorted(b.difference(&a)), vec![5]);
+        assert_eq!(sorted(a.symmetric_difference(&b)), vec![1, 2, 5]);
+
+        assert!(!a.is_disjoint(&b));
+        assert!(a.is_disjoint(&HashSet::from([6])));
+        assert!(HashSet::from([3, 4]).is_subset(&a));
+        assert!(a.is_superset(&HashSet::from([3, 4])));
+        assert!(!a.is_subset(&b));
+    }
+
+    #[test]
+    fn hashset_eq_and_serde() {
+        let forward: HashSet<i32> = (0..50).collect();
+        let mut backward = HashSet::with_capacity(500);
+        backward.extend((0..50).rev());
+        assert_eq!(forward, backward);
+
+        let json = serde_json::to_string(&forward).unwrap();
+        assert!(json.starts_with('['));
+        let round_trip: HashSet<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_trip, forward);
+
+        let duplicates: HashSet<OwnedStr> = serde_json::from_str(r#"["a", "b", "a"]"#).unwrap();
+        assert_eq!(duplicates.len(), 2);
+
+        let std_set: std::collections::HashSet<i32> = forward.clone().into();
+        assert_eq!(HashSet::from(std_set), forward);
+    }
+}
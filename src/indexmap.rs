@@ -0,0 +1,775 @@
+use serde::de::{MapAccess, Visitor};
+use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::ops::Index;
+use std::{fmt, mem, vec};
+
+use crate::hashmap::{
+    bucket_capacity, capacity_to_buckets, cautious_capacity, DefaultHashBuilder, KeyValue,
+    DEFAULT_MAX_LOAD,
+};
+use crate::owned_slice::OwnedSlice;
+use crate::owned_vec::OwnedVec;
+
+pub use crate::entries::{IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+
+/// A hash map that keeps its entries in insertion order, with a layout that
+/// cbindgen can describe.
+///
+/// The entries live in a plain `OwnedVec<KeyValue<K, V>>`, so C++ can walk
+/// them as a span, in order, without knowing about the hash index. The index
+/// is a separate open-addressed table of positions into the entries, probed
+/// linearly.
+///
+/// If hashing, comparing keys or a callback panics, the index is rebuilt
+/// from the entries, so the map stays usable.
+///
+/// Two maps are equal if they hold the same entries, regardless of their
+/// order.
+#[repr(C)]
+pub struct IndexMap<K, V, S = DefaultHashBuilder> {
+    entries: OwnedVec<KeyValue<K, V>>,
+    // The position of an entry in `entries` plus one, or zero for an empty
+    // slot. The number of slots is zero or a power of two, and at most 3/4
+    // of them are used.
+    indices: OwnedSlice<usize>,
+    _hasher: PhantomData<S>,
+}
+
+/// Rebuilds the index of the map when dropped during a panic, in case the
+/// panic left it out of sync with the entries.
+struct RebuildOnUnwind<'a, K: Hash + Eq, V, S: BuildHasher + Default>(&'a mut IndexMap<K, V, S>);
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> Drop for RebuildOnUnwind<'_, K, V, S> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.0.rebuild_index(self.0.indices.len());
+        }
+    }
+}
+
+/// Looks up `key` in the index table `indices` of `entries`.
+///
+/// Returns the slot and the position of the entry, or the empty slot where
+/// the probe sequence ended. `indices` must have slots.
+fn probe<K, V, Q>(
+    entries: &[KeyValue<K, V>],
+    indices: &[usize],
+    hash: u64,
+    key: &Q,
+) -> Result<(usize, usize), usize>
+where
+    K: Borrow<Q>,
+    Q: Eq + ?Sized,
+{
+    let mask = indices.len() - 1;
+    let mut slot = hash as usize & mask;
+    loop {
+        match indices[slot] {
+            0 => return Err(slot),
+            index if entries[index - 1].key.borrow() == key => return Ok((slot, index - 1)),
+            _ => slot = (slot + 1) & mask,
+        }
+    }
+}
+
+impl<K, V> IndexMap<K, V> {
+    /// Creates an empty map. It does not allocate until the first insert.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates an empty map that can hold at least `capacity` entries
+    /// without reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::allocate(capacity)
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> IndexMap<K, V, S> {
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, the entry is appended and
+    /// `None` is returned. If the map did have this key present, the value is
+    /// updated in place and the old value is returned. Neither the key nor
+    /// the position of the entry is updated.
+    pub fn insert(&mut self, key: K, value: V) -> std::option::Option<V> {
+        self.insert_full(key, value).1
+    }
+
+    /// Inserts a key-value pair into the map, and returns the position of
+    /// the entry along with the old value, if any.
+    pub fn insert_full(&mut self, key: K, value: V) -> (usize, std::option::Option<V>) {
+        if self.entries.len() >= bucket_capacity(self.indices.len(), DEFAULT_MAX_LOAD) {
+            let buckets = capacity_to_buckets(self.entries.len() + 1, DEFAULT_MAX_LOAD)
+                .expect("Hash table capacity overflow.");
+            self.indices = self.build_index(&self.entries, buckets);
+        }
+        let hash = self.hash(&key);
+        match probe(&self.entries, &self.indices, hash, &key) {
+            Ok((_, index)) => (
+                index,
+                Some(mem::replace(&mut self.entries[index].value, value)),
+            ),
+            Err(slot) => {
+                self.entries.push(KeyValue { key, value });
+                self.indices[slot] = self.entries.len();
+                (self.entries.len() - 1, None)
+            }
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    pub fn get<Q>(&self, key: &Q) -> std::option::Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.get_index_of(key)?;
+        Some(&self.entries[index].value)
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    pub fn get_key_value<Q>(&self, key: &Q) -> std::option::Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.get_index_of(key)?;
+        let entry = &self.entries[index];
+        Some((&entry.key, &entry.value))
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> std::option::Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.get_index_of(key)?;
+        Some(&mut self.entries[index].value)
+    }
+
+    /// Returns the position of the entry for the key.
+    pub fn get_index_of<Q>(&self, key: &Q) -> std::option::Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).map(|(_, index)| index)
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Removes a key from the map and returns its value, moving the last
+    /// entry into its position.
+    ///
+    /// This does not preserve the order of the entries, but does not shift
+    /// them either.
+    pub fn swap_remove<Q>(&mut self, key: &Q) -> std::option::Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.swap_remove_entry(key).map(|(_, value)| value)
+    }
+
+    /// Removes a key from the map and returns the stored key and value,
+    /// moving the last entry into its position.
+    pub fn swap_remove_entry<Q>(&mut self, key: &Q) -> std::option::Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (slot, index) = self.find(key)?;
+        let map = RebuildOnUnwind(self);
+        map.0.remove_slot(slot);
+        let last = map.0.entries.len() - 1;
+        if index != last {
+            let hash = map.0.hash(&map.0.entries[last].key);
+            let moved = map.0.slot_of(hash, last);
+            map.0.indices[moved] = index + 1;
+        }
+        let entry = map.0.entries.swap_remove(index);
+        Some((entry.key, entry.value))
+    }
+
+    /// Removes a key from the map and returns its value, shifting all the
+    /// following entries down by one.
+    ///
+    /// This preserves the order of the entries, but takes linear time.
+    pub fn shift_remove<Q>(&mut self, key: &Q) -> std::option::Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shift_remove_entry(key).map(|(_, value)| value)
+    }
+
+    /// Removes a key from the map and returns the stored key and value,
+    /// shifting all the following entries down by one.
+    pub fn shift_remove_entry<Q>(&mut self, key: &Q) -> std::option::Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (slot, index) = self.find(key)?;
+        let map = RebuildOnUnwind(self);
+        map.0.remove_slot(slot);
+        for position in map.0.indices.iter_mut() {
+            if *position > index + 1 {
+                *position -= 1;
+            }
+        }
+        let entry = map.0.entries.remove(index);
+        Some((entry.key, entry.value))
+    }
+
+    /// Retains only the elements specified by the predicate, keeping the
+    /// order of the remaining entries.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let map = RebuildOnUnwind(self);
+        map.0
+            .entries
+            .retain_mut(|entry| f(&entry.key, &mut entry.value));
+        map.0.rebuild_index(map.0.indices.len());
+    }
+
+    /// Sorts the entries by their keys.
+    pub fn sort_keys(&mut self)
+    where
+        K: Ord,
+    {
+        self.sort_by(|key1, _, key2, _| key1.cmp(key2));
+    }
+
+    /// Sorts the entries with a comparison function on their keys and
+    /// values. The sort is stable.
+    pub fn sort_by<F>(&mut self, mut compare: F)
+    where
+        F: FnMut(&K, &V, &K, &V) -> Ordering,
+    {
+        let map = RebuildOnUnwind(self);
+        map.0
+            .entries
+            .sort_by(|a, b| compare(&a.key, &a.value, &b.key, &b.value));
+        map.0.rebuild_index(map.0.indices.len());
+    }
+
+    #[inline]
+    fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        S::default().hash_one(key)
+    }
+
+    /// Returns the slot and the position of the entry for `key`.
+    fn find<Q>(&self, key: &Q) -> std::option::Option<(usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.entries.is_empty() {
+            return None;
+        }
+        probe(&self.entries, &self.indices, self.hash(key), key).ok()
+    }
+
+    /// Returns the slot that points to the entry at `index`, whose key hashes
+    /// to `hash`.
+    fn slot_of(&self, hash: u64, index: usize) -> usize {
+        let mask = self.indices.len() - 1;
+        let mut slot = hash as usize & mask;
+        while self.indices[slot] != index + 1 {
+            slot = (slot + 1) & mask;
+        }
+        slot
+    }
+
+    /// Empties the slot at `slot`, shifting the following slots back so that
+    /// every entry stays reachable from its home slot.
+    fn remove_slot(&mut self, mut hole: usize) {
+        let mask = self.indices.len() - 1;
+        self.indices[hole] = 0;
+        let mut next = (hole + 1) & mask;
+        while self.indices[next] != 0 {
+            let home = self.hash(&self.entries[self.indices[next] - 1].key) as usize & mask;
+            // The entry can fill the hole if the hole lies between its home
+            // slot and its current slot.
+            if next.wrapping_sub(home) & mask >= next.wrapping_sub(hole) & mask {
+                self.indices[hole] = mem::replace(&mut self.indices[next], 0);
+                hole = next;
+            }
+            next = (next + 1) & mask;
+        }
+    }
+
+    /// Rebuilds the index with `buckets` slots, or fewer if the entries fit.
+    fn rebuild_index(&mut self, buckets: usize) {
+        let needed = capacity_to_buckets(self.entries.len(), DEFAULT_MAX_LOAD)
+            .expect("Hash table capacity overflow.");
+        self.indices = self.build_index(&self.entries, buckets.max(needed));
+    }
+
+    /// Builds an index with `buckets` slots for `entries`.
+    fn build_index(&self, entries: &[KeyValue<K, V>], buckets: usize) -> OwnedSlice<usize> {
+        let mut indices: OwnedSlice<usize> = vec![0; buckets].into();
+        let mask = buckets.saturating_sub(1);
+        for (index, entry) in entries.iter().enumerate() {
+            let mut slot = self.hash(&entry.key) as usize & mask;
+            while indices[slot] != 0 {
+                slot = (slot + 1) & mask;
+            }
+            indices[slot] = index + 1;
+        }
+        indices
+    }
+}
+
+impl<K, V, S> IndexMap<K, V, S> {
+    /// Creates an empty map that can hold at least `capacity` entries
+    /// without reallocating, hashing with `S`.
+    fn allocate(capacity: usize) -> Self {
+        let buckets =
+            capacity_to_buckets(capacity, DEFAULT_MAX_LOAD).expect("Hash table capacity overflow.");
+        IndexMap {
+            entries: OwnedVec::with_capacity(capacity),
+            indices: vec![0; buckets].into(),
+            _hasher: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the entry at position `index`.
+    pub fn get_index(&self, index: usize) -> std::option::Option<(&K, &V)> {
+        let entry = self.entries.get(index)?;
+        Some((&entry.key, &entry.value))
+    }
+
+    /// Returns the entry at position `index`, with a mutable reference to
+    /// the value.
+    pub fn get_index_mut(&mut self, index: usize) -> std::option::Option<(&K, &mut V)> {
+        let entry = self.entries.get_mut(index)?;
+        Some((&entry.key, &mut entry.value))
+    }
+
+    /// Returns the first entry.
+    pub fn first(&self) -> std::option::Option<(&K, &V)> {
+        self.get_index(0)
+    }
+
+    /// Returns the last entry.
+    pub fn last(&self) -> std::option::Option<(&K, &V)> {
+        self.get_index(self.entries.len().checked_sub(1)?)
+    }
+
+    /// The entries in order. This is the span C++ sees.
+    pub fn as_slice(&self) -> &[KeyValue<K, V>] {
+        &self.entries
+    }
+
+    /// An iterator visiting all key-value pairs in order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.entries.iter(),
+        }
+    }
+
+    /// An iterator visiting all key-value pairs in order, with mutable
+    /// references to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            inner: self.entries.iter_mut(),
+        }
+    }
+
+    /// An iterator visiting all keys in order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys {
+            inner: self.entries.iter(),
+        }
+    }
+
+    /// An iterator visiting all values in order.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values {
+            inner: self.entries.iter(),
+        }
+    }
+
+    /// An iterator visiting all values mutably in order.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.entries.iter_mut(),
+        }
+    }
+}
+
+impl<K: Clone, V: Clone, S> Clone for IndexMap<K, V, S> {
+    fn clone(&self) -> Self {
+        IndexMap {
+            entries: self.entries.clone(),
+            indices: self.indices.clone(),
+            _hasher: PhantomData,
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for IndexMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S> Default for IndexMap<K, V, S> {
+    fn default() -> Self {
+        Self::allocate(0)
+    }
+}
+
+impl<K: Hash + Eq, V: PartialEq, S: BuildHasher + Default> PartialEq for IndexMap<K, V, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K: Hash + Eq, V: Eq, S: BuildHasher + Default> Eq for IndexMap<K, V, S> {}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default, Q> Index<&Q> for IndexMap<K, V, S>
+where
+    K: Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `IndexMap`.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("Key not found in IndexMap.")
+    }
+}
+
+impl<K, V, S> IntoIterator for IndexMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            inner: self.entries.into_vec().into_iter(),
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a IndexMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut IndexMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> FromIterator<(K, V)> for IndexMap<K, V, S> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = IndexMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> Extend<(K, V)> for IndexMap<K, V, S> {
+    /// Inserts all key-value pairs of the iterator in order.
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        let iter = iter.into_iter();
+        self.entries.reserve(iter.size_hint().0);
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq + Copy, V: Copy, S: BuildHasher + Default> Extend<(&'a K, &'a V)>
+    for IndexMap<K, V, S>
+{
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    }
+}
+
+impl<K: Hash + Eq, V, const N: usize> From<[(K, V); N]> for IndexMap<K, V> {
+    fn from(entries: [(K, V); N]) -> Self {
+        IndexMap::from_iter(entries)
+    }
+}
+
+impl<K, V, H> Serialize for IndexMap<K, V, H>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+struct MapVisitor<K, V, S> {
+    #[allow(clippy::type_complexity)]
+    marker: PhantomData<fn() -> IndexMap<K, V, S>>,
+}
+
+impl<'de, K, V, S> Visitor<'de> for MapVisitor<K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    type Value = IndexMap<K, V, S>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        let mut map = IndexMap::allocate(cautious_capacity(access.size_hint()));
+
+        while let Some((key, value)) = access.next_entry()? {
+            map.insert(key, value);
+        }
+
+        Ok(map)
+    }
+}
+
+impl<'de, K, V, S> Deserialize<'de> for IndexMap<K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(MapVisitor {
+            marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::owned_str::OwnedStr;
+
+    use super::IndexMap;
+
+    fn keys<V>(map: &IndexMap<i32, V>) -> Vec<i32> {
+        map.keys().copied().collect()
+    }
+
+    #[test]
+    fn indexmap_insertion_order() {
+        let mut map = IndexMap::new();
+        for key in [5, 3, 9, 1, 7] {
+            assert_eq!(map.insert(key, key * 10), None);
+        }
+        assert_eq!(map.insert(3, 31), Some(30));
+        assert_eq!(keys(&map), vec![5, 3, 9, 1, 7]);
+        assert_eq!(map.get_index(1), Some((&3, &31)));
+        assert_eq!(map.get_index_of(&9), Some(2));
+        assert_eq!(map.get_index(5), None);
+        assert_eq!(map.first(), Some((&5, &50)));
+        assert_eq!(map.last(), Some((&7, &70)));
+        assert_eq!(map[&1], 10);
+        assert_eq!(map.get(&2), None);
+        assert_eq!(map.as_slice()[2].key, 9);
+        assert_eq!(map.iter().next_back(), Some((&7, &70)));
+
+        let map: IndexMap<i32, i32> = (0..1000).rev().map(|i| (i, i)).collect();
+        assert!(map.keys().copied().eq((0..1000).rev()));
+        for i in 0..1000 {
+            assert_eq!(map.get_index_of(&i), Some(999 - i as usize));
+        }
+    }
+
+    #[test]
+    fn indexmap_remove() {
+        let mut map: IndexMap<i32, i32> = (0..10).map(|i| (i, i)).collect();
+        assert_eq!(map.swap_remove(&2), Some(2));
+        assert_eq!(keys(&map), vec![0, 1, 9, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(map.get_index_of(&9), Some(2));
+        assert_eq!(map.shift_remove(&3), Some(3));
+        assert_eq!(keys(&map), vec![0, 1, 9, 4, 5, 6, 7, 8]);
+        assert_eq!(map.get_index_of(&8), Some(7));
+        assert_eq!(map.swap_remove(&8), Some(8));
+        assert_eq!(map.shift_remove(&3), None);
+        for (index, key) in keys(&map).into_iter().enumerate() {
+            assert_eq!(map.get_index_of(&key), Some(index));
+        }
+
+        map.retain(|key, _| key % 2 == 0);
+        assert_eq!(keys(&map), vec![0, 4, 6]);
+        assert_eq!(map[&6], 6);
+
+        let mut map: IndexMap<i32, i32> = (0..500).map(|i| (i, i)).collect();
+        for i in (0..500).step_by(3) {
+            assert_eq!(map.swap_remove(&i), Some(i));
+        }
+        for i in (1..500).step_by(3) {
+            assert_eq!(map.shift_remove(&i), Some(i));
+        }
+        for i in 0..500 {
+            let expected = if i % 3 == 2 { Some(&i) } else { None };
+            assert_eq!(map.get(&i), expected);
+        }
+    }
+
+    #[test]
+    fn indexmap_panic_keeps_index() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        fn check(map: &IndexMap<i32, i32>) {
+            for (index, key) in keys(map).into_iter().enumerate() {
+                assert_eq!(map.get_index_of(&key), Some(index));
+            }
+        }
+
+        let mut map: IndexMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            map.retain(|&key, _| {
+                assert_ne!(key, 50);
+                key % 2 == 0
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(map.len(), 75);
+        check(&map);
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            map.sort_by(|key1, _, key2, _| {
+                assert!(*key1 != 60 && *key2 != 60);
+                key2.cmp(key1)
+            })
+        }));
+        assert!(result.is_err());
+        check(&map);
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            map.extend((100..110).map(|i| {
+                assert_ne!(i, 105);
+                (i, i)
+            }))
+        }));
+        assert!(result.is_err());
+        assert_eq!(map.len(), 80);
+        assert_eq!(map.get_index_of(&104), Some(79));
+        check(&map);
+    }
+
+    #[test]
+    fn indexmap_sort_keys() {
+        let mut map: IndexMap<i32, &str> = IndexMap::from([(3, "c"), (1, "a"), (2, "b")]);
+        map.sort_keys();
+        assert_eq!(keys(&map), vec![1, 2, 3]);
+        assert_eq!(map[&3], "c");
+        map.sort_by(|_, value1, _, value2| value2.cmp(value1));
+        assert_eq!(keys(&map), vec![3, 2, 1]);
+        assert_eq!(map.get_index_of(&1), Some(2));
+    }
+
+    #[test]
+    fn indexmap_deserialize_caps_size_hint() {
+        use serde::de::value::{Error, MapAccessDeserializer};
+        use serde::de::{DeserializeSeed, MapAccess};
+        use serde::Deserialize;
+
+        /// An empty map that claims to hold `usize::MAX` entries.
+        struct HugeHint;
+
+        impl<'de> MapAccess<'de> for HugeHint {
+            type Error = Error;
+
+            fn next_key_seed<K: DeserializeSeed<'de>>(
+                &mut self,
+                _seed: K,
+            ) -> Result<Option<K::Value>, Error> {
+                Ok(None)
+            }
+
+            fn next_value_seed<V: DeserializeSeed<'de>>(
+                &mut self,
+                _seed: V,
+            ) -> Result<V::Value, Error> {
+                unreachable!("There are no keys.")
+            }
+
+            fn size_hint(&self) -> Option<usize> {
+                Some(usize::MAX)
+            }
+        }
+
+        let map = IndexMap::<u64, u64>::deserialize(MapAccessDeserializer::new(HugeHint)).unwrap();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn indexmap_serde_preserves_order() {
+        let record = r#"{"z":1,"a":2,"m":3,"a":4}"#;
+        let map: IndexMap<OwnedStr, i32> = serde_json::from_str(record).unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(
+            serde_json::to_string(&map).unwrap(),
+            r#"{"z":1,"a":4,"m":3}"#
+        );
+
+        let mut reordered = map.clone();
+        reordered.sort_by(|_, value1, _, value2| value1.cmp(value2));
+        assert_eq!(reordered, map);
+        assert_eq!(
+            format!("{:?}", IndexMap::from([(2, 1), (1, 2)])),
+            "{2: 1, 1: 2}"
+        );
+    }
+}
@@ -1,4 +1,14 @@
+pub mod arc_slice;
+pub mod arc_str;
+pub mod borrowed;
+pub mod entries;
+pub mod fxhash;
 pub mod hashmap;
+pub mod hashset;
+pub mod indexmap;
 pub mod option;
+pub mod owned_box;
 pub mod owned_slice;
 pub mod owned_str;
+pub mod owned_vec;
+pub mod sortedmap;
@@ -23,10 +23,10 @@ impl<T> From<std::option::Option<T>> for Option<T> {
     }
 }
 
-impl<T> Into<std::option::Option<T>> for Option<T> {
+impl<T> From<Option<T>> for std::option::Option<T> {
     #[inline]
-    fn into(self) -> std::option::Option<T> {
-        match self {
+    fn from(value: Option<T>) -> Self {
+        match value {
             Option::None => None,
             Option::Some(val) => Some(val),
         }
@@ -60,8 +60,6 @@ impl<T> Option<T> {
     /// # Examples
     ///
     /// ```
-    /// #![feature(is_some_and)]
-    ///
     /// let x: Option<u32> = Some(2);
     /// assert_eq!(x.is_some_and(|x| x > 1), true);
     ///
@@ -339,8 +337,6 @@ impl<T> Option<T> {
     /// # Examples
     ///
     /// ```
-    /// #![feature(result_option_inspect)]
-    ///
     /// let v = vec![1, 2, 3, 4, 5];
     ///
     /// // prints "got: 4"
@@ -872,15 +868,15 @@ impl<T> Option<T> {
     /// # Examples
     ///
     /// ```
-    /// #![feature(option_result_contains)]
+    /// use crust_std::option::Option;
     ///
-    /// let x: Option<u32> = Some(2);
+    /// let x: Option<u32> = Option::Some(2);
     /// assert_eq!(x.contains(&2), true);
     ///
-    /// let x: Option<u32> = Some(3);
+    /// let x: Option<u32> = Option::Some(3);
     /// assert_eq!(x.contains(&2), false);
     ///
-    /// let x: Option<u32> = None;
+    /// let x: Option<u32> = Option::None;
     /// assert_eq!(x.contains(&2), false);
     /// ```
     #[must_use]
@@ -925,7 +921,7 @@ impl<T> Option<T> {
     /// # Examples
     ///
     /// ```
-    /// #![feature(option_zip)]
+    /// use crust_std::option::Option;
     ///
     /// #[derive(Debug, PartialEq)]
     /// struct Point {
@@ -939,11 +935,11 @@ impl<T> Option<T> {
     ///     }
     /// }
     ///
-    /// let x = Some(17.5);
-    /// let y = Some(42.7);
+    /// let x = Option::Some(17.5);
+    /// let y = Option::Some(42.7);
     ///
-    /// assert_eq!(x.zip_with(y, Point::new), Some(Point { x: 17.5, y: 42.7 }));
-    /// assert_eq!(x.zip_with(None, Point::new), None);
+    /// assert_eq!(x.clone().zip_with(y, Point::new), Some(Point { x: 17.5, y: 42.7 }));
+    /// assert_eq!(x.zip_with(Option::None, Point::new), None);
     /// ```
     pub fn zip_with<U, F, R>(self, other: Option<U>, f: F) -> std::option::Option<R>
     where
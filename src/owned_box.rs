@@ -0,0 +1,311 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+#![allow(unsafe_code)]
+
+//! A replacement for `Box<T>` that cbindgen can understand.
+
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::borrow::{Borrow, BorrowMut};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::{fmt, mem};
+
+/// A struct that replaces a `Box<T>`, so that recursive types can be
+/// described to cbindgen.
+///
+/// `std::option::Option<OwnedBox<T>>` is pointer-sized. In the FFI layout,
+/// optional boxes are stored in the tagged [`crate::option::Option`].
+///
+/// cbindgen would turn this transparent struct into a plain typedef, which
+/// could not have a destructor, so it is excluded from the generated
+/// bindings and the C++ template is written by hand in `cbindgen.toml`. Its
+/// destructor calls the drop function that
+/// [`export_owned_box!`](crate::export_owned_box) exports for the element
+/// type.
+#[repr(transparent)]
+pub struct OwnedBox<T: Sized> {
+    ptr: NonNull<T>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Sized> Drop for OwnedBox<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { drop(Box::from_raw(self.ptr.as_ptr())) }
+    }
+}
+
+unsafe impl<T: Sized + Send> Send for OwnedBox<T> {}
+unsafe impl<T: Sized + Sync> Sync for OwnedBox<T> {}
+
+impl<T: Clone> Clone for OwnedBox<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::new(self.deref().clone())
+    }
+}
+
+impl<T: Default> Default for OwnedBox<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OwnedBox<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.deref().fmt(formatter)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for OwnedBox<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.deref().fmt(formatter)
+    }
+}
+
+impl<T: PartialEq> PartialEq for OwnedBox<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref().eq(other.deref())
+    }
+}
+
+impl<T: Eq> Eq for OwnedBox<T> {}
+
+impl<T: Hash> Hash for OwnedBox<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.deref().hash(state)
+    }
+}
+
+impl<T: Sized> OwnedBox<T> {
+    /// Moves `value` to the heap.
+    #[inline]
+    pub fn new(value: T) -> Self {
+        Self::from(Box::new(value))
+    }
+
+    /// Convert the OwnedBox into a Box.
+    #[inline]
+    pub fn into_box(self) -> Box<T> {
+        let ret = unsafe { Box::from_raw(self.ptr.as_ptr()) };
+        mem::forget(self);
+        ret
+    }
+
+    /// Moves the value out of the box.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        *self.into_box()
+    }
+}
+
+impl<T> Deref for OwnedBox<T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for OwnedBox<T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> AsRef<T> for OwnedBox<T> {
+    #[inline]
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T> AsMut<T> for OwnedBox<T> {
+    #[inline]
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T> Borrow<T> for OwnedBox<T> {
+    #[inline]
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T> BorrowMut<T> for OwnedBox<T> {
+    #[inline]
+    fn borrow_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T> From<Box<T>> for OwnedBox<T> {
+    #[inline]
+    fn from(b: Box<T>) -> Self {
+        Self {
+            ptr: unsafe { NonNull::new_unchecked(Box::into_raw(b)) },
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> From<T> for OwnedBox<T> {
+    #[inline]
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Exports a C function that drops an `OwnedBox<$type>`, under the symbol
+/// name `crust_owned_box_<prefix>_drop`.
+///
+/// The C++ destructor of `iceberg::OwnedBox<T>` calls it once the matching
+/// `CRUST_EXPORT_OWNED_BOX(T, PREFIX)` has been used, with the same prefix.
+///
+/// ```
+/// use crust_std::{export_owned_box, owned_str::OwnedStr};
+///
+/// export_owned_box!(OwnedStr, prefix = "doc_str");
+/// ```
+#[macro_export]
+macro_rules! export_owned_box {
+    ($type:ty, prefix = $prefix:literal) => {
+        const _: () = {
+            use $crate::owned_box::OwnedBox;
+
+            /// # Safety
+            ///
+            /// `owned_box` must point to a live box, which must not be used
+            /// afterwards.
+            #[export_name = concat!("crust_owned_box_", $prefix, "_drop")]
+            pub unsafe extern "C" fn drop(owned_box: *mut OwnedBox<$type>) {
+                owned_box.drop_in_place();
+            }
+        };
+    };
+}
+
+export_owned_box!(crate::owned_str::OwnedStr, prefix = "str");
+
+impl<T: Serialize> Serialize for OwnedBox<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.deref().serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for OwnedBox<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let r = Box::<T>::deserialize(deserializer)?;
+        Ok(r.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::option::Option;
+    use crate::owned_slice::OwnedSlice;
+    use crate::owned_str::OwnedStr;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "type", rename_all = "lowercase")]
+    enum Expr {
+        Literal {
+            value: i64,
+        },
+        Not {
+            child: OwnedBox<Expr>,
+        },
+        And {
+            left: OwnedBox<Expr>,
+            right: OwnedBox<Expr>,
+        },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Field {
+        name: OwnedStr,
+        #[serde(default)]
+        element: std::option::Option<OwnedBox<Field>>,
+    }
+
+    #[test]
+    fn owned_box() {
+        let record = r#"
+        {"type": "and",
+         "left": {"type": "literal", "value": 1},
+         "right": {"type": "not", "child": {"type": "literal", "value": 2}}}
+        "#;
+
+        let result: Expr = serde_json::from_str(record).unwrap();
+        let Expr::And { right, .. } = &result else {
+            panic!("Expected an and expression");
+        };
+        assert_eq!(
+            **right,
+            Expr::Not {
+                child: Expr::Literal { value: 2 }.into()
+            }
+        );
+        let result_two: Expr = serde_json::from_str(
+            &serde_json::to_string(&result).expect("Failed to serialize result"),
+        )
+        .expect("Failed to serialize json");
+        assert_eq!(result, result_two);
+        assert_eq!(result.clone(), result_two);
+
+        let mut boxed = OwnedBox::new(OwnedSlice::from(vec![1, 2]));
+        boxed[0] = 3;
+        assert_eq!(*boxed.into_inner(), [3, 2]);
+    }
+
+    #[test]
+    fn owned_box_drop_ffi() {
+        extern "C" {
+            fn crust_owned_box_str_drop(owned_box: *mut OwnedBox<OwnedStr>);
+        }
+
+        let mut boxed = mem::ManuallyDrop::new(OwnedBox::new(OwnedStr::from("boxed".to_string())));
+        // SAFETY: the box is live and is not used afterwards.
+        unsafe { crust_owned_box_str_drop(&mut *boxed) };
+    }
+
+    #[test]
+    fn owned_box_in_option() {
+        assert_eq!(
+            mem::size_of::<std::option::Option<OwnedBox<u64>>>(),
+            mem::size_of::<usize>()
+        );
+        assert_eq!(
+            mem::size_of::<Option<OwnedBox<u64>>>(),
+            2 * mem::size_of::<usize>()
+        );
+
+        let list: Field =
+            serde_json::from_str(r#"{"name": "list", "element": {"name": "element"}}"#).unwrap();
+        assert_eq!(&*list.element.as_ref().unwrap().name, "element");
+        assert!(list.element.unwrap().element.is_none());
+
+        let some = Option::Some(OwnedBox::new(7));
+        let none: Option<OwnedBox<i32>> = Option::None;
+        assert_eq!(some, Option::Some(OwnedBox::new(7)));
+        assert_ne!(some, none);
+    }
+}
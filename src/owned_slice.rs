@@ -8,6 +8,7 @@
 
 use serde::de::{Deserialize, Deserializer};
 use serde::ser::{Serialize, Serializer};
+use std::alloc::{self, Layout};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::ptr::NonNull;
@@ -23,6 +24,10 @@ use std::{fmt, iter, mem, slice};
 /// But handling fat pointers with cbindgen both in structs and argument
 /// positions more generally is a bit tricky.
 ///
+/// The elements are allocated by Rust's global allocator, whatever it is. C++
+/// must allocate and release them through [`crust_alloc`] and
+/// [`crust_dealloc`], never with `malloc` and `free`.
+///
 /// cbindgen:derive-eq=false
 /// cbindgen:derive-neq=false
 #[repr(C)]
@@ -47,7 +52,7 @@ impl<T: Sized> Drop for OwnedSlice<T> {
     #[inline]
     fn drop(&mut self) {
         if self.len != 0 {
-            let _ = mem::replace(self, Self::default()).into_vec();
+            let _ = mem::take(self).into_vec();
         }
     }
 }
@@ -58,7 +63,7 @@ unsafe impl<T: Sized + Sync> Sync for OwnedSlice<T> {}
 impl<T: Clone> Clone for OwnedSlice<T> {
     #[inline]
     fn clone(&self) -> Self {
-        Self::from_slice(&**self)
+        Self::from_slice(self)
     }
 }
 
@@ -91,12 +96,6 @@ impl<T: Sized> OwnedSlice<T> {
         ret
     }
 
-    /// Iterate over all the elements in the slice taking ownership of them.
-    #[inline]
-    pub fn into_iter(self) -> impl Iterator<Item = T> + ExactSizeIterator {
-        self.into_vec().into_iter()
-    }
-
     /// Convert the regular slice into an owned slice.
     #[inline]
     pub fn from_slice(s: &[T]) -> Self
@@ -107,6 +106,37 @@ impl<T: Sized> OwnedSlice<T> {
     }
 }
 
+/// Allocates `size` bytes aligned to `align` with Rust's global allocator, so
+/// that C++ can build an `OwnedSlice` that Rust is able to drop. Returns null
+/// if the allocation fails, and a dangling, aligned pointer if `size` is zero.
+///
+/// # Safety
+///
+/// `align` must be a power of two, and `size` rounded up to `align` must not
+/// overflow `isize`.
+#[no_mangle]
+pub unsafe extern "C" fn crust_alloc(size: usize, align: usize) -> *mut u8 {
+    if size == 0 {
+        return sptr::invalid_mut(align);
+    }
+    alloc::alloc(Layout::from_size_align_unchecked(size, align))
+}
+
+/// Releases memory allocated by Rust's global allocator, such as the elements
+/// of an `OwnedSlice<T>`, which take `len * sizeof(T)` bytes aligned to
+/// `alignof(T)`. Does nothing if `size` is zero.
+///
+/// # Safety
+///
+/// Unless `size` is zero, `ptr` must have been allocated by [`crust_alloc`]
+/// or the global allocator with the same `size` and `align`.
+#[no_mangle]
+pub unsafe extern "C" fn crust_dealloc(ptr: *mut u8, size: usize, align: usize) {
+    if size != 0 {
+        alloc::dealloc(ptr, Layout::from_size_align_unchecked(size, align));
+    }
+}
+
 impl<T> Deref for OwnedSlice<T> {
     type Target = [T];
 
@@ -144,6 +174,17 @@ impl<T> From<Vec<T>> for OwnedSlice<T> {
     }
 }
 
+impl<T> IntoIterator for OwnedSlice<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    /// Iterate over all the elements in the slice taking ownership of them.
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_vec().into_iter()
+    }
+}
+
 impl<T> iter::FromIterator<T> for OwnedSlice<T> {
     #[inline]
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
@@ -188,4 +229,39 @@ mod tests {
         .expect("Failed to serialize json");
         assert_eq!(result, result_two);
     }
+
+    #[test]
+    fn owned_slice_alloc_dealloc() {
+        let values = OwnedSlice::from(vec![1u64, 2, 3]);
+        let len = values.len();
+        let ptr = values.ptr.as_ptr();
+        mem::forget(values);
+        // SAFETY: the slice was allocated by the global allocator for
+        // `len` elements of `u64`.
+        unsafe {
+            crust_dealloc(
+                ptr.cast(),
+                len * mem::size_of::<u64>(),
+                mem::align_of::<u64>(),
+            );
+        }
+
+        // SAFETY: the layout is valid, and the memory is initialized before
+        // it is handed to the `OwnedSlice`, which releases it on drop.
+        let values = unsafe {
+            let ptr = crust_alloc(4 * mem::size_of::<u32>(), mem::align_of::<u32>()).cast::<u32>();
+            for i in 0..4 {
+                ptr.add(i).write(i as u32);
+            }
+            OwnedSlice::from(Vec::from_raw_parts(ptr, 4, 4))
+        };
+        assert_eq!(&*values, &[0, 1, 2, 3]);
+
+        // SAFETY: zero-sized allocations are never passed to the allocator.
+        unsafe {
+            let ptr = crust_alloc(0, 8);
+            assert_eq!(ptr as usize, 8);
+            crust_dealloc(ptr, 0, 8);
+        }
+    }
 }
@@ -0,0 +1,454 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+#![allow(unsafe_code)]
+
+//! A replacement for `Vec<T>` that cbindgen can understand.
+
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::alloc::{self, Layout};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::{fmt, iter, mem, slice};
+
+use crate::owned_slice::OwnedSlice;
+
+/// A growable vector with a layout that cbindgen can describe.
+///
+/// It is a `Vec<T>` taken apart into its pointer, length and capacity, so it
+/// converts to and from `Vec<T>` without copying. Converting from an
+/// [`OwnedSlice`] is free as well, while converting to one shrinks the
+/// allocation to the length first.
+///
+/// C++ appends through `push_back`, which grows the buffer with
+/// [`crust_owned_vec_reserve`], so the elements always live in memory of
+/// Rust's global allocator. Growing moves the elements bytewise, so C++ may
+/// only store types that Rust could move the same way.
+///
+/// cbindgen:derive-eq=false
+/// cbindgen:derive-neq=false
+#[repr(C)]
+pub struct OwnedVec<T: Sized> {
+    ptr: NonNull<T>,
+    len: usize,
+    cap: usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Sized> Default for OwnedVec<T> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            ptr: NonNull::dangling(),
+            len: 0,
+            cap: 0,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Sized> Drop for OwnedVec<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { drop(Vec::from_raw_parts(self.ptr.as_ptr(), self.len, self.cap)) }
+    }
+}
+
+unsafe impl<T: Sized + Send> Send for OwnedVec<T> {}
+unsafe impl<T: Sized + Sync> Sync for OwnedVec<T> {}
+
+impl<T: Clone> Clone for OwnedVec<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::from(self.to_vec())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OwnedVec<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.deref().fmt(formatter)
+    }
+}
+
+impl<T: PartialEq> PartialEq for OwnedVec<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref().eq(other.deref())
+    }
+}
+
+impl<T: Eq> Eq for OwnedVec<T> {}
+
+impl<T: Sized> OwnedVec<T> {
+    /// Creates an empty vector. It does not allocate until the first push.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty vector with room for at least `capacity` elements.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::from(Vec::with_capacity(capacity))
+    }
+
+    /// Returns the number of elements the vector can hold without
+    /// reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// Appends an element to the back of the vector.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.with_vec(|vec| vec.push(value))
+    }
+
+    /// Removes the last element and returns it, or `None` if the vector is
+    /// empty.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.with_vec(|vec| vec.pop())
+    }
+
+    /// Inserts an element at `index`, shifting all elements after it to the
+    /// right.
+    ///
+    /// Panics if `index > len`.
+    #[inline]
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.with_vec(|vec| vec.insert(index, value))
+    }
+
+    /// Removes and returns the element at `index`, shifting all elements
+    /// after it to the left.
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> T {
+        self.with_vec(|vec| vec.remove(index))
+    }
+
+    /// Removes and returns the element at `index`, moving the last element
+    /// into its place.
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        self.with_vec(|vec| vec.swap_remove(index))
+    }
+
+    /// Keeps only the elements for which `f` returns true, in order.
+    #[inline]
+    pub fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, f: F) {
+        self.with_vec(|vec| vec.retain_mut(f))
+    }
+
+    /// Removes all but the first of consecutive elements for which
+    /// `same_bucket` returns true. It is called with the later element
+    /// first, as in `Vec::dedup_by`.
+    #[inline]
+    pub fn dedup_by<F: FnMut(&mut T, &mut T) -> bool>(&mut self, same_bucket: F) {
+        self.with_vec(|vec| vec.dedup_by(same_bucket))
+    }
+
+    /// Shortens the vector to `len` elements and drops the rest. Does
+    /// nothing if the vector is not longer than `len`.
+    #[inline]
+    pub fn truncate(&mut self, len: usize) {
+        self.with_vec(|vec| vec.truncate(len))
+    }
+
+    /// Drops all elements and keeps the allocation.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.truncate(0)
+    }
+
+    /// Reserves room for at least `additional` more elements.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.with_vec(|vec| vec.reserve(additional))
+    }
+
+    /// Convert the OwnedVec into a Vec.
+    #[inline]
+    pub fn into_vec(self) -> Vec<T> {
+        let ret = unsafe { Vec::from_raw_parts(self.ptr.as_ptr(), self.len, self.cap) };
+        mem::forget(self);
+        ret
+    }
+
+    /// Convert the OwnedVec into an OwnedSlice, shrinking the allocation to
+    /// the length.
+    #[inline]
+    pub fn into_owned_slice(self) -> OwnedSlice<T> {
+        self.into_vec().into()
+    }
+
+    /// Runs `f` on the vector as a `Vec`. The vector is put back even if `f`
+    /// panics.
+    fn with_vec<R>(&mut self, f: impl FnOnce(&mut Vec<T>) -> R) -> R {
+        let vec = mem::take(self).into_vec();
+        let mut guard = PutBack { target: self, vec };
+        f(&mut guard.vec)
+    }
+}
+
+/// Moves `vec` back into `target` when dropped.
+struct PutBack<'a, T> {
+    target: &'a mut OwnedVec<T>,
+    vec: Vec<T>,
+}
+
+impl<T> Drop for PutBack<'_, T> {
+    fn drop(&mut self) {
+        *self.target = mem::take(&mut self.vec).into();
+    }
+}
+
+/// Makes room for at least `additional` more elements in `vec`, whose
+/// elements take `size` bytes each and are aligned to `align`. C++ calls it
+/// for every `OwnedVec<T>`, cast to `OwnedVec<uint8_t>`, because the layout
+/// of the vector does not depend on `T`.
+///
+/// The capacity at least doubles when the vector grows, so appending is
+/// amortized constant time. Aborts if the capacity overflows or the
+/// allocation fails.
+///
+/// # Safety
+///
+/// `vec` must have been created by Rust for elements of `size` bytes aligned
+/// to `align`, and `size` must be a multiple of `align`.
+#[no_mangle]
+pub unsafe extern "C" fn crust_owned_vec_reserve(
+    vec: &mut OwnedVec<u8>,
+    additional: usize,
+    size: usize,
+    align: usize,
+) {
+    let required = vec
+        .len
+        .checked_add(additional)
+        .expect("Vector capacity overflow.");
+    if size == 0 || required <= vec.cap {
+        return;
+    }
+    let cap = required.max(vec.cap * 2).max(4);
+    let layout = cap
+        .checked_mul(size)
+        .and_then(|bytes| Layout::from_size_align(bytes, align).ok())
+        .expect("Vector capacity overflow.");
+    let ptr = if vec.cap == 0 {
+        alloc::alloc(layout)
+    } else {
+        let old_layout = Layout::from_size_align_unchecked(vec.cap * size, align);
+        alloc::realloc(vec.ptr.as_ptr(), old_layout, layout.size())
+    };
+    vec.ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+    vec.cap = cap;
+}
+
+impl<T> Deref for OwnedVec<T> {
+    type Target = [T];
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for OwnedVec<T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> From<Vec<T>> for OwnedVec<T> {
+    #[inline]
+    fn from(vec: Vec<T>) -> Self {
+        let mut vec = mem::ManuallyDrop::new(vec);
+        Self {
+            ptr: unsafe { NonNull::new_unchecked(vec.as_mut_ptr()) },
+            len: vec.len(),
+            cap: vec.capacity(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> From<OwnedVec<T>> for Vec<T> {
+    #[inline]
+    fn from(vec: OwnedVec<T>) -> Self {
+        vec.into_vec()
+    }
+}
+
+impl<T> From<OwnedSlice<T>> for OwnedVec<T> {
+    #[inline]
+    fn from(slice: OwnedSlice<T>) -> Self {
+        Self::from(slice.into_vec())
+    }
+}
+
+impl<T> From<OwnedVec<T>> for OwnedSlice<T> {
+    #[inline]
+    fn from(vec: OwnedVec<T>) -> Self {
+        vec.into_owned_slice()
+    }
+}
+
+impl<T> IntoIterator for OwnedVec<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    /// Iterate over all the elements in the vector taking ownership of them.
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_vec().into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a OwnedVec<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> iter::FromIterator<T> for OwnedVec<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Vec::from_iter(iter).into()
+    }
+}
+
+impl<T> Extend<T> for OwnedVec<T> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.with_vec(|vec| vec.extend(iter))
+    }
+}
+
+impl<T: Serialize> Serialize for OwnedVec<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.deref().serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for OwnedVec<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let r = Vec::<T>::deserialize(deserializer)?;
+        Ok(r.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::owned_str::OwnedStr;
+
+    #[test]
+    fn owned_vec() {
+        let mut vec = OwnedVec::new();
+        for i in 0..10 {
+            vec.push(i);
+        }
+        vec.insert(0, 100);
+        assert_eq!(vec.remove(5), 4);
+        assert_eq!(vec.pop(), Some(9));
+        vec.extend([20, 21]);
+        vec.truncate(4);
+        assert_eq!(&*vec, &[100, 0, 1, 2]);
+        vec.reserve(100);
+        assert!(vec.capacity() >= 104);
+        vec.clear();
+        assert!(vec.is_empty());
+        assert_eq!(vec.pop(), None);
+
+        let record = r#"
+        [1,2,3,4,5]
+        "#;
+
+        let result: OwnedVec<i32> = serde_json::from_str(record).unwrap();
+        assert_eq!(3, result[2]);
+        let result_two: OwnedVec<i32> = serde_json::from_str(
+            &serde_json::to_string(&result).expect("Failed to serialize result"),
+        )
+        .expect("Failed to serialize json");
+        assert_eq!(result, result_two);
+    }
+
+    #[test]
+    fn owned_vec_panic_keeps_elements() {
+        let mut vec: OwnedVec<OwnedStr> = (0..4).map(|i| format!("s{i}").into()).collect();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            vec.retain_mut(|s| {
+                assert_ne!(&**s, "s2");
+                &**s != "s1"
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(vec.len(), 3);
+        assert_eq!(&*vec.swap_remove(0), "s0");
+        assert_eq!(&*vec[0], "s3");
+    }
+
+    #[test]
+    fn owned_vec_conversions() {
+        let mut vec = Vec::with_capacity(8);
+        vec.extend([1, 2, 3]);
+        let ptr = vec.as_ptr();
+
+        let owned = OwnedVec::from(vec);
+        assert_eq!(owned.capacity(), 8);
+        let vec = owned.into_vec();
+        assert_eq!((vec.as_ptr(), vec.capacity()), (ptr, 8));
+
+        let slice = OwnedSlice::from(vec![1, 2, 3]);
+        let ptr = slice.as_ptr();
+        let mut owned = OwnedVec::from(slice);
+        assert_eq!(owned.as_ptr(), ptr);
+        owned.push(4);
+        let slice = OwnedSlice::from(owned);
+        assert_eq!(&*slice, &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn owned_vec_reserve_ffi() {
+        let mut vec: OwnedVec<OwnedStr> = OwnedVec::new();
+        for i in 0..20 {
+            // SAFETY: `OwnedVec<OwnedStr>` has the layout of `OwnedVec<u8>`,
+            // and the elements are described by their own size and
+            // alignment. The new element is written into reserved capacity.
+            unsafe {
+                let raw = &mut *(&mut vec as *mut OwnedVec<OwnedStr>).cast::<OwnedVec<u8>>();
+                crust_owned_vec_reserve(
+                    raw,
+                    1,
+                    mem::size_of::<OwnedStr>(),
+                    mem::align_of::<OwnedStr>(),
+                );
+                vec.ptr.as_ptr().add(vec.len).write(format!("s{i}").into());
+                vec.len += 1;
+            }
+        }
+        assert!(vec.capacity() >= 20);
+        assert_eq!(&*vec[19], "s19");
+        vec.push("s20".to_string().into());
+        assert_eq!(vec.len(), 21);
+    }
+}
@@ -0,0 +1,570 @@
+use serde::de::{MapAccess, Visitor};
+use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::marker::PhantomData;
+use std::ops::{Bound, Index, RangeBounds};
+use std::{fmt, mem};
+
+pub use crate::entries::{IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+use crate::hashmap::{cautious_capacity, KeyValue};
+use crate::owned_vec::OwnedVec;
+
+/// A map that keeps its entries sorted by key, with a layout that cbindgen
+/// can describe.
+///
+/// The entries live in a plain `OwnedVec<KeyValue<K, V>>` in ascending key
+/// order, so C++ can walk them as a span and binary search them itself.
+/// Lookups are binary searches. Inserts and removals shift the following
+/// entries, so build large maps with [`FromIterator`] or [`Extend`], which
+/// sort once per call.
+///
+/// A panic in a closure, an iterator or `Ord::cmp` never loses entries that
+/// were in the map. Only a panicking `Ord::cmp` in [`Extend`] can leave them
+/// out of order.
+#[derive(Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct SortedMap<K, V> {
+    entries: OwnedVec<KeyValue<K, V>>,
+}
+
+/// Sorts `entries` by key and removes duplicate keys, keeping the entry that
+/// came last.
+fn sort_dedup<K: Ord, V>(entries: &mut OwnedVec<KeyValue<K, V>>) {
+    // The sort is stable, so the last entry of every run of equal keys is
+    // the one that was inserted last.
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    entries.reverse();
+    entries.dedup_by(|later, earlier| later.key == earlier.key);
+    entries.reverse();
+}
+
+impl<K, V> SortedMap<K, V> {
+    /// Creates an empty map. It does not allocate until the first insert.
+    pub fn new() -> Self {
+        SortedMap {
+            entries: OwnedVec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the entry with the smallest key.
+    pub fn first_key_value(&self) -> std::option::Option<(&K, &V)> {
+        let entry = self.entries.first()?;
+        Some((&entry.key, &entry.value))
+    }
+
+    /// Returns the entry with the largest key.
+    pub fn last_key_value(&self) -> std::option::Option<(&K, &V)> {
+        let entry = self.entries.last()?;
+        Some((&entry.key, &entry.value))
+    }
+
+    /// Removes and returns the entry with the smallest key.
+    pub fn pop_first(&mut self) -> std::option::Option<(K, V)> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let entry = self.remove_at(0);
+        Some((entry.key, entry.value))
+    }
+
+    /// Removes and returns the entry with the largest key.
+    pub fn pop_last(&mut self) -> std::option::Option<(K, V)> {
+        let entry = self.entries.pop()?;
+        Some((entry.key, entry.value))
+    }
+
+    /// Retains only the elements specified by the predicate.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.entries
+            .retain_mut(|entry| f(&entry.key, &mut entry.value));
+    }
+
+    /// The entries in ascending key order. This is the span C++ sees.
+    pub fn as_slice(&self) -> &[KeyValue<K, V>] {
+        &self.entries
+    }
+
+    /// An iterator visiting all key-value pairs in ascending key order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.entries.iter(),
+        }
+    }
+
+    /// An iterator visiting all key-value pairs in ascending key order, with
+    /// mutable references to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            inner: self.entries.iter_mut(),
+        }
+    }
+
+    /// An iterator visiting all keys in ascending order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys {
+            inner: self.entries.iter(),
+        }
+    }
+
+    /// An iterator visiting all values in ascending key order.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values {
+            inner: self.entries.iter(),
+        }
+    }
+
+    /// An iterator visiting all values mutably in ascending key order.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.entries.iter_mut(),
+        }
+    }
+
+    fn remove_at(&mut self, index: usize) -> KeyValue<K, V> {
+        self.entries.remove(index)
+    }
+}
+
+impl<K: Ord, V> SortedMap<K, V> {
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, `None` is returned. If the
+    /// map did have this key present, the value is updated and the old value
+    /// is returned. The key is not updated.
+    pub fn insert(&mut self, key: K, value: V) -> std::option::Option<V> {
+        match self.search(&key) {
+            Ok(index) => Some(mem::replace(&mut self.entries[index].value, value)),
+            Err(index) => {
+                self.entries.insert(index, KeyValue { key, value });
+                None
+            }
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    pub fn get<Q>(&self, key: &Q) -> std::option::Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let index = self.search(key).ok()?;
+        Some(&self.entries[index].value)
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    pub fn get_key_value<Q>(&self, key: &Q) -> std::option::Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let entry = &self.entries[self.search(key).ok()?];
+        Some((&entry.key, &entry.value))
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> std::option::Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let index = self.search(key).ok()?;
+        Some(&mut self.entries[index].value)
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.search(key).is_ok()
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    pub fn remove<Q>(&mut self, key: &Q) -> std::option::Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> std::option::Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let index = self.search(key).ok()?;
+        let entry = self.remove_at(index);
+        Some((entry.key, entry.value))
+    }
+
+    /// An iterator over the entries whose keys lie in `range`, in ascending
+    /// key order.
+    ///
+    /// Unlike `BTreeMap::range`, an empty or inverted range yields no
+    /// entries instead of panicking.
+    pub fn range<Q, R>(&self, range: R) -> Iter<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let (start, end) = self.range_indices(range);
+        Iter {
+            inner: self.entries[start..end].iter(),
+        }
+    }
+
+    /// A mutable iterator over the entries whose keys lie in `range`, in
+    /// ascending key order.
+    pub fn range_mut<Q, R>(&mut self, range: R) -> IterMut<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let (start, end) = self.range_indices(range);
+        IterMut {
+            inner: self.entries[start..end].iter_mut(),
+        }
+    }
+
+    /// Binary searches for `key`, returning its position or the position
+    /// where it would be inserted.
+    fn search<Q>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.entries
+            .binary_search_by(|entry| entry.key.borrow().cmp(key))
+    }
+
+    /// Returns the positions of the first entry in `range` and of the first
+    /// entry after it.
+    fn range_indices<Q, R>(&self, range: R) -> (usize, usize)
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(start) => self
+                .entries
+                .partition_point(|entry| entry.key.borrow() < start),
+            Bound::Excluded(start) => self
+                .entries
+                .partition_point(|entry| entry.key.borrow() <= start),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => self
+                .entries
+                .partition_point(|entry| entry.key.borrow() <= end),
+            Bound::Excluded(end) => self
+                .entries
+                .partition_point(|entry| entry.key.borrow() < end),
+            Bound::Unbounded => self.entries.len(),
+        };
+        (start, end.max(start))
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for SortedMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V> Default for SortedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V, Q> Index<&Q> for SortedMap<K, V>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `SortedMap`.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("Key not found in SortedMap.")
+    }
+}
+
+impl<K, V> IntoIterator for SortedMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            inner: self.entries.into_vec().into_iter(),
+        }
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a SortedMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a mut SortedMap<K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for SortedMap<K, V> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = SortedMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for SortedMap<K, V> {
+    /// Inserts all key-value pairs of the iterator. If a key occurs more than
+    /// once, the last value wins, as with repeated calls to `insert`.
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        // The new entries are sorted on their own first, so a panic in the
+        // iterator or in the first sort leaves the map untouched.
+        let mut entries: OwnedVec<KeyValue<K, V>> = iter
+            .into_iter()
+            .map(|(key, value)| KeyValue { key, value })
+            .collect();
+        sort_dedup(&mut entries);
+        if self.entries.is_empty() {
+            self.entries = entries;
+        } else {
+            self.entries.extend(entries);
+            sort_dedup(&mut self.entries);
+        }
+    }
+}
+
+impl<'a, K: Ord + Copy, V: Copy> Extend<(&'a K, &'a V)> for SortedMap<K, V> {
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for SortedMap<K, V> {
+    fn from(entries: [(K, V); N]) -> Self {
+        SortedMap::from_iter(entries)
+    }
+}
+
+impl<K: Ord, V> From<std::collections::BTreeMap<K, V>> for SortedMap<K, V> {
+    fn from(map: std::collections::BTreeMap<K, V>) -> Self {
+        let entries: OwnedVec<KeyValue<K, V>> = map
+            .into_iter()
+            .map(|(key, value)| KeyValue { key, value })
+            .collect();
+        SortedMap { entries }
+    }
+}
+
+impl<K: Ord, V> From<SortedMap<K, V>> for std::collections::BTreeMap<K, V> {
+    fn from(map: SortedMap<K, V>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl<K, V> Serialize for SortedMap<K, V>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+struct MapVisitor<K, V> {
+    marker: PhantomData<fn() -> SortedMap<K, V>>,
+}
+
+impl<'de, K, V> Visitor<'de> for MapVisitor<K, V>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+{
+    type Value = SortedMap<K, V>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        let mut entries = OwnedVec::with_capacity(cautious_capacity(access.size_hint()));
+
+        while let Some((key, value)) = access.next_entry()? {
+            entries.push(KeyValue { key, value });
+        }
+
+        sort_dedup(&mut entries);
+        Ok(SortedMap { entries })
+    }
+}
+
+impl<'de, K, V> Deserialize<'de> for SortedMap<K, V>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(MapVisitor {
+            marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use super::SortedMap;
+
+    fn keys<'a>(iter: impl Iterator<Item = (&'a i32, &'a i32)>) -> Vec<i32> {
+        iter.map(|(key, _)| *key).collect()
+    }
+
+    #[test]
+    fn sortedmap_insert_get_remove() {
+        let mut map = SortedMap::new();
+        for key in [5, 3, 9, 1, 7] {
+            assert_eq!(map.insert(key, key * 10), None);
+        }
+        assert_eq!(map.insert(3, 31), Some(30));
+        assert_eq!(keys(map.iter()), vec![1, 3, 5, 7, 9]);
+        assert_eq!(map[&3], 31);
+        assert_eq!(map.get(&4), None);
+        assert_eq!(map.first_key_value(), Some((&1, &10)));
+        assert_eq!(map.last_key_value(), Some((&9, &90)));
+        assert_eq!(map.remove(&5), Some(50));
+        assert_eq!(map.remove(&5), None);
+        assert_eq!(map.pop_first(), Some((1, 10)));
+        assert_eq!(map.pop_last(), Some((9, 90)));
+        assert_eq!(keys(map.iter()), vec![3, 7]);
+        assert_eq!(SortedMap::<i32, i32>::new().pop_first(), None);
+        assert_eq!(format!("{:?}", map), "{3: 31, 7: 70}");
+    }
+
+    #[test]
+    fn sortedmap_range() {
+        let map: SortedMap<i32, i32> = (0..20).rev().map(|i| (i * 2, i)).collect();
+        assert_eq!(keys(map.range(3..9)), vec![4, 6, 8]);
+        assert_eq!(keys(map.range(4..=8)), vec![4, 6, 8]);
+        assert_eq!(keys(map.range(..3)), vec![0, 2]);
+        assert_eq!(keys(map.range(35..)), vec![36, 38]);
+        assert_eq!(
+            keys(map.range((Bound::Excluded(4), Bound::Excluded(10)))),
+            vec![6, 8]
+        );
+        assert_eq!(
+            map.range((Bound::Included(9), Bound::Excluded(3))).count(),
+            0
+        );
+        assert_eq!(map.range(100..).count(), 0);
+
+        let mut map = map;
+        for (_, value) in map.range_mut(10..14) {
+            *value = -1;
+        }
+        assert_eq!(map.values().filter(|value| **value == -1).count(), 2);
+    }
+
+    #[test]
+    fn sortedmap_from_iter_last_wins() {
+        let map = SortedMap::from([(2, 1), (1, 1), (2, 2), (3, 1), (2, 3)]);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&2], 3);
+
+        let std_map: std::collections::BTreeMap<i32, i32> = map.clone().into();
+        assert_eq!(SortedMap::from(std_map), map);
+    }
+
+    #[test]
+    fn sortedmap_panic_keeps_entries() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut map: SortedMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            map.retain(|&key, _| {
+                assert_ne!(key, 50);
+                key % 2 == 0
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(map.len(), 75);
+        assert_eq!(keys(map.range(46..54)), vec![46, 48, 50, 51, 52, 53]);
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            map.extend((100..110).map(|i| {
+                assert_ne!(i, 105);
+                (i, i)
+            }))
+        }));
+        assert!(result.is_err());
+        assert_eq!(map.len(), 75);
+        assert_eq!(map.last_key_value(), Some((&99, &99)));
+
+        map.extend([(51, -1), (120, 120), (-1, -1)]);
+        assert_eq!(map.len(), 77);
+        assert_eq!(map[&51], -1);
+        assert_eq!(map.first_key_value(), Some((&-1, &-1)));
+        assert_eq!(map.last_key_value(), Some((&120, &120)));
+    }
+
+    #[test]
+    fn sortedmap_serde() {
+        let map: SortedMap<String, i32> =
+            serde_json::from_str(r#"{"b": 1, "c": 2, "a": 3, "b": 4}"#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&map).unwrap(),
+            r#"{"a":3,"b":4,"c":2}"#
+        );
+    }
+}
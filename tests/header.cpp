@@ -0,0 +1,107 @@
+// Compiled and linked against the static library by `tests/header.rs`, to
+// check that the prototypes that `crust_std.h` declares by hand match the
+// functions exported from Rust.
+
+#include <cstdio>
+#include <cstring>
+#include <memory>
+#include <new>
+#include <string_view>
+#include <utility>
+
+#include "crust_std.h"
+
+using namespace iceberg;
+
+#define CHECK(condition)                                                                           \
+  if (!(condition)) {                                                                              \
+    std::fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition);            \
+    return 1;                                                                                      \
+  }
+
+static OwnedStr make_str(std::string_view s) {
+  OwnedStr str(crust_alloc(s.size(), 1), s.size());
+  std::memcpy(str.ptr, s.data(), s.size());
+  return str;
+}
+
+static std::string_view view(const OwnedStr &s) {
+  return { reinterpret_cast<const char *>(s.ptr), s.len };
+}
+
+template<typename V>
+using Map = HashMap<OwnedStr, V, DefaultHashBuilder>;
+
+static int hashmap_str_i64() {
+  alignas(Map<int64_t>) unsigned char storage[sizeof(Map<int64_t>)];
+  auto &map = *reinterpret_cast<Map<int64_t> *>(storage);
+  map.init();
+  for (int64_t i = 0; i < 10; ++i) {
+    char key[4];
+    int len = std::snprintf(key, sizeof(key), "k%d", static_cast<int>(i));
+    int64_t value = i;
+    CHECK(map.insert(make_str({ key, static_cast<size_t>(len) }), std::move(value)));
+  }
+  OwnedStr key = make_str("k3");
+  CHECK(map.size() == 10);
+  CHECK(map.contains(key));
+  CHECK(*map.get(key) == 3);
+
+  int64_t sum = 0;
+  map.for_each([&](const OwnedStr &, const int64_t &value) { sum += value; });
+  CHECK(sum == 45);
+
+  int64_t removed = 0;
+  CHECK(map.remove(key, &removed));
+  CHECK(removed == 3);
+  CHECK(!map.remove(key));
+  CHECK(map.get(key) == nullptr);
+
+  map.clear();
+  CHECK(map.size() == 0);
+  std::destroy_at(&map);
+  return 0;
+}
+
+static int hashmap_str_str() {
+  alignas(Map<OwnedStr>) unsigned char storage[sizeof(Map<OwnedStr>)];
+  auto &map = *reinterpret_cast<Map<OwnedStr> *>(storage);
+  map.init();
+  CHECK(map.insert(make_str("key"), make_str("value")));
+  CHECK(!map.insert(make_str("key"), make_str("other")));
+
+  OwnedStr key = make_str("key");
+  alignas(OwnedStr) unsigned char removed[sizeof(OwnedStr)];
+  CHECK(map.remove(key, reinterpret_cast<OwnedStr *>(removed)));
+  OwnedStr value = std::move(*reinterpret_cast<OwnedStr *>(removed));
+  reinterpret_cast<OwnedStr *>(removed)->~OwnedStr();
+  CHECK(view(value) == "other");
+  std::destroy_at(&map);
+  return 0;
+}
+
+static int owned_box_str() {
+  alignas(OwnedBox<OwnedStr>) unsigned char storage[sizeof(OwnedBox<OwnedStr>)];
+  auto &boxed = *reinterpret_cast<OwnedBox<OwnedStr> *>(storage);
+  boxed.ptr = reinterpret_cast<OwnedStr *>(crust_alloc(sizeof(OwnedStr), alignof(OwnedStr)));
+  ::new (boxed.ptr) OwnedStr(make_str("boxed"));
+  CHECK(view(*boxed) == "boxed");
+  boxed.~OwnedBox();
+  return 0;
+}
+
+static int str_and_vec() {
+  CHECK(Str::FromUtf8("h\xc3\xa9llo").has_value());
+  CHECK(!Str::FromUtf8("\xc3").has_value());
+
+  OwnedVec<OwnedStr> vec;
+  for (int i = 0; i < 20; ++i)
+    vec.push_back(make_str("s"));
+  CHECK(vec.len == 20);
+  CHECK(vec.cap >= 20);
+  return 0;
+}
+
+int main() {
+  return hashmap_str_i64() || hashmap_str_str() || owned_box_str() || str_and_vec();
+}
@@ -0,0 +1,66 @@
+//! Compiles `tests/header.cpp` against `crust_std.h` and links it with the
+//! crate built as a static library, so that the C++ prototypes are checked
+//! against the symbols that Rust actually exports.
+//!
+//! The test is skipped if no C++20 compiler is found. It uses `$CXX`, or
+//! `c++` if that is not set.
+
+use std::env;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::Command;
+
+#[cfg(unix)]
+#[test]
+fn header_links_against_exported_symbols() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("header");
+    let cxx = env::var("CXX").unwrap_or_else(|_| "c++".to_string());
+
+    match Command::new(&cxx).arg("--version").output() {
+        Ok(output) if output.status.success() => {}
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            eprintln!("Skipping the header test: {cxx} was not found.");
+            return;
+        }
+        result => panic!("Failed to run {cxx}: {result:?}"),
+    }
+
+    // A separate target directory, so that the build does not wait for the
+    // lock that the running `cargo test` holds.
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(cargo)
+        .current_dir(manifest_dir)
+        .args([
+            "rustc",
+            "--lib",
+            "--crate-type",
+            "staticlib",
+            "--target-dir",
+        ])
+        .arg(&out_dir)
+        .status()
+        .expect("Failed to run cargo.");
+    assert!(status.success(), "Building the static library failed.");
+
+    let exe = out_dir.join("header");
+    let status = Command::new(&cxx)
+        .arg("-std=c++20")
+        .arg("-I")
+        .arg(manifest_dir)
+        .arg(manifest_dir.join("tests/header.cpp"))
+        .arg(out_dir.join("debug/libcrust_std.a"))
+        .args(["-lpthread", "-ldl", "-o"])
+        .arg(&exe)
+        .status()
+        .expect("Failed to run the C++ compiler.");
+    assert!(
+        status.success(),
+        "Compiling or linking tests/header.cpp failed."
+    );
+
+    let status = Command::new(&exe)
+        .status()
+        .expect("Failed to run the test.");
+    assert!(status.success(), "tests/header.cpp failed.");
+}